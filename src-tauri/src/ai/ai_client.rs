@@ -7,17 +7,25 @@ use std::collections::HashMap;
 use std::process::{Command, Stdio};
 use std::io::{BufReader, BufRead};
 
-use log::{debug, error, info};
+use indexmap::IndexMap;
+use log::{error, info};
+use serde::Serialize;
 use serde_json::json;
 use reqwest::Client;
 use serde_json::Value;
 use futures_util::StreamExt;
 
 use crate::context::Context;
-use crate::ai::privacy;
-use crate::db::conn::establish_connection;
-use crate::db::ai_token_usage::increment_used_token;
+use crate::context::assembler::ScoredItem;
+use crate::context::outbound::OutboundFields;
+use crate::context::source::placeholder;
+use crate::ai::{egress, privacy};
+use crate::db::{ai_token_usage, writer};
+use crate::db::app_consent::ConsentDecision;
+use crate::db::vault;
+use crate::db::privacy_audit::NewPrivacyAudit;
 use crate::config::{self, ai_client::AiProvider};
+use crate::utils::logger::redact;
 
 struct StreamingDeanonymizer<F>
 where
@@ -92,14 +100,74 @@ where
     }
 }
 
+/// 发往 API 的完整请求, 发送和预览共用同一份构造结果
+struct ApiRequest {
+    url: String,
+    headers: IndexMap<String, String>,
+    body: String,
+    anonymized_prompt: String,
+    mapping: HashMap<String, String>,
+    detections: Vec<privacy::Detection>,
+}
+
+/// 请求预览: 展示将要离开本机的内容, 不会真正发送
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestPreview {
+    pub provider: String,
+    pub target: String,
+    pub headers: IndexMap<String, String>,
+    pub body: String,
+    pub redactions: usize,
+    // 仅本地模式下会被拒绝时的原因
+    pub blocked: Option<String>,
+    // 上下文候选的得分和取舍
+    pub selection: Vec<ScoredItem>,
+}
+
+fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..3].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}****{}", head, tail)
+}
+
+// 一次请求的提供方、模型和 prompt 长度, 用于记录补全事件
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    pub provider: &'static str,
+    pub model: String,
+    pub prompt_tokens: i64,
+}
+
 pub struct AiClient {
     // 未来可扩展：API 地址配置、异步请求、mock/真实切换等
+    // 当前应用被授权为仅本地时, 即使全局未开启仅本地模式也只允许本地提供方
+    local_only: bool,
 }
 
 impl AiClient {
     pub fn new() -> Self {
         info!("[AiClient::new] creating new AiClient");
-        AiClient {}
+        AiClient { local_only: false }
+    }
+
+    pub fn with_local_only(mut self, local_only: bool) -> Self {
+        self.local_only = local_only;
+        self
+    }
+
+    pub fn request_info(&self, context: &Context) -> RequestInfo {
+        let config = config::get_config().unwrap().ai_client;
+        let prompt_tokens = self.prompt_text(context.clone()).chars().count() as i64;
+        let (provider, model) = match config.provider {
+            AiProvider::API if config.api_key.is_empty() => ("MOCK", String::new()),
+            AiProvider::API => ("API", config.api_model),
+            AiProvider::CMD => ("CMD", config.cmd.split_whitespace().next().unwrap_or_default().to_string()),
+        };
+        RequestInfo { provider, model, prompt_tokens }
     }
 
     pub async fn stream_request_ai<F>(&self, context: Context, on_token: F, cancel_token: Arc<AtomicBool>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
@@ -130,29 +198,24 @@ impl AiClient {
         info!("[AiClient::stream_request_mock] starting mock stream request");
         
         let config = config::get_config().unwrap().ai_client;
-        let apikey = config.api_key.clone();
-        let mut conn = establish_connection();
+        let profile = ai_token_usage::profile_id(&config);
         let prompt = self.prompt_text(context.clone());
         let prompt_token_count = prompt.chars().count() as i64;
-        increment_used_token(&mut conn, &apikey, prompt_token_count);
-
-        let history_first = context.history.first();
-        let clipboard_first = context.clipboard_history.first();
-        let mock_response = if let Some(h) = history_first {
-            h.input_content.clone()
-        } else if let Some(c) = clipboard_first {
-            c.clone()
-        } else {
-            String::new()
-        };
+        writer::increment_used_token(&profile, prompt_token_count);
+
+        let mock_response = context.sources
+            .iter()
+            .find_map(|s| s.items.first())
+            .map(|item| item.text.clone())
+            .unwrap_or_default();
         
-        info!("[AiClient::stream_request_mock] mock response: {}", mock_response);
+        info!("[AiClient::stream_request_mock] mock response: {}", redact(&mock_response));
 
         let chars: Vec<String> = mock_response.chars().map(|c| c.to_string()).collect();
         std::thread::spawn(move || {
             for c in chars {
                 if cancel_token.load(Ordering::SeqCst) { break; }
-                increment_used_token(&mut conn, &apikey, c.chars().count() as i64);
+                writer::increment_used_token(&profile, c.chars().count() as i64);
                 on_token(c.clone());
                 thread::sleep(Duration::from_millis(50));
             }
@@ -160,25 +223,84 @@ impl AiClient {
         Ok(())
     }
 
+    /// 构造请求但不发送, 返回与真实请求一致的请求体和(脱敏后的)请求头
+    pub fn preview_request(&self, context: Context) -> RequestPreview {
+        let config = config::get_config().unwrap().ai_client;
+        let selection = context.selection.clone();
+        let prompt = self.prompt_text(context);
+        let blocked = egress::check_egress(&config, self.local_only).err();
+        match config.provider {
+            AiProvider::API if config.api_key.is_empty() => RequestPreview {
+                provider: "MOCK".to_string(),
+                target: String::new(),
+                headers: IndexMap::new(),
+                body: String::new(),
+                redactions: 0,
+                blocked,
+                selection,
+            },
+            AiProvider::API => {
+                let request = self.build_api_request(&prompt);
+                let mut headers = request.headers;
+                headers.insert("Authorization".to_string(), format!("Bearer {}", mask_secret(&config.api_key)));
+                RequestPreview {
+                    provider: "API".to_string(),
+                    target: request.url,
+                    headers,
+                    body: request.body,
+                    redactions: request.mapping.len(),
+                    blocked,
+                    selection,
+                }
+            }
+            AiProvider::CMD => RequestPreview {
+                provider: "CMD".to_string(),
+                target: config.cmd,
+                headers: IndexMap::new(),
+                body: prompt,
+                redactions: 0,
+                blocked,
+                selection,
+            },
+        }
+    }
+
+    fn build_api_request(&self, prompt: &str) -> ApiRequest {
+        let config = config::get_config().unwrap().ai_client;
+        let anonymized_data = privacy::anonymize(prompt);
+        let body = json!({
+            "model": config.api_model,
+            "messages": [
+                { "role": "user", "content": anonymized_data.text }
+            ],
+            "stream": true
+        }).to_string();
+        let mut headers = IndexMap::new();
+        headers.insert("Authorization".to_string(), format!("Bearer {}", config.api_key));
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        ApiRequest {
+            url: config.api_url,
+            headers,
+            body,
+            anonymized_prompt: anonymized_data.text,
+            mapping: anonymized_data.mapping,
+            detections: anonymized_data.detections,
+        }
+    }
+
     fn prompt_text(&self, context: Context) -> String {
         let config = config::get_config().unwrap().ai_client;
-        let app_name = context.app.window_app;
-        let window_title = context.app.window_title;
-        let window_handle = context.app.window_id;
-        let input_title = context.app.input_title;
-        let input_content = context.app.input_content;
-        let input_handle = context.app.input_id;
-        let input_history = json!(&context.history).to_string();
-        let clipboard_contents = json!(&context.clipboard_history).to_string();
+        let fields = OutboundFields::from_context(&context);
         let mut prompt = config.prompt;
-        prompt = prompt.replace("{{app_name}}", &app_name);
-        prompt = prompt.replace("{{window_title}}", &window_title);
-        prompt = prompt.replace("{{window_handle}}", &window_handle);
-        prompt = prompt.replace("{{input_title}}", &input_title);
-        prompt = prompt.replace("{{input_handle}}", &input_handle);
-        prompt = prompt.replace("{{input_content}}", &input_content);
-        prompt = prompt.replace("{{input_history}}", &input_history);
-        prompt = prompt.replace("{{clipboard_contents}}", &clipboard_contents);
+        prompt = prompt.replace("{{app_name}}", &fields.app_name);
+        prompt = prompt.replace("{{window_title}}", &fields.window_title);
+        prompt = prompt.replace("{{window_handle}}", &fields.window_handle);
+        prompt = prompt.replace("{{input_title}}", &fields.input_title);
+        prompt = prompt.replace("{{input_handle}}", &fields.input_handle);
+        prompt = prompt.replace("{{input_content}}", &fields.input_content);
+        for (name, value) in &fields.sources {
+            prompt = prompt.replace(&placeholder(name), value);
+        }
         prompt
     }
 
@@ -188,6 +310,8 @@ impl AiClient {
     {
         info!("[AiClient::stream_cmd] starting CLI stream request");
         let config = config::get_config().unwrap().ai_client;
+        egress::check_egress(&config, self.local_only)?;
+        let app = context.app.window_app.clone();
         let prompt = self.prompt_text(context);
         let command_str = config.cmd;
 
@@ -203,7 +327,9 @@ impl AiClient {
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
 
-        info!("[AiClient::stream_cmd] command: {:?}, prompt: {}", cmd, prompt);
+        info!("[AiClient::stream_cmd] command: {:?}, prompt: {}", cmd, redact(&prompt));
+
+        writer::insert_audit(NewPrivacyAudit::new("CMD", executable, &app, &prompt, &[]));
 
         let mut child = cmd.spawn()?;
         if let Some(mut stdin) = child.stdin.take() {
@@ -244,50 +370,46 @@ impl AiClient {
     {
         info!("[AiClient::stream_api] starting AI stream request");
         let config = config::get_config().unwrap().ai_client;
+        egress::check_egress(&config, self.local_only)?;
+        let app = context.app.window_app.clone();
         let prompt = self.prompt_text(context);
 
-        let anonymized_data = privacy::anonymize(&prompt);
-        let anonymized_prompt = anonymized_data.text;
-        info!("[AiClient::stream_api] anonymized_prompt: {:?}", anonymized_prompt);
-        let mapping = anonymized_data.mapping;
-        debug!("[AiClient::stream_api] mapping: {:?}", mapping);
+        let api_request = self.build_api_request(&prompt);
+        info!("[AiClient::stream_api] request body: {}", redact(&api_request.body));
+        let mut mapping = api_request.mapping;
+        // 历史中已入库脱敏的值以保险库占位符出现, 输出时一并还原
+        mapping.extend(vault::resolve_placeholders(&api_request.anonymized_prompt));
+        info!("[AiClient::stream_api] {} values anonymized", api_request.detections.len());
 
         enum Processor {
             Passthrough(Box<dyn FnMut(String) + Send>),
             Deanonymizing(StreamingDeanonymizer<Box<dyn FnMut(String) + Send>>),
         }
 
-        let apikey = config.api_key.clone();
-        let mut conn = establish_connection();
+        let profile = ai_token_usage::profile_id(&config);
+        writer::insert_audit(NewPrivacyAudit::new("API", &config.api_model, &app, &api_request.anonymized_prompt, &api_request.detections));
         let prompt_token_count = prompt.chars().count() as i64;
-        increment_used_token(&mut conn, &apikey, prompt_token_count);
+        writer::increment_used_token(&profile, prompt_token_count);
         let mut processor = if mapping.is_empty() {
             let mut f = on_token;
             Processor::Passthrough(Box::new(move |token: String| {
-                increment_used_token(&mut conn, &apikey, token.chars().count() as i64);
+                writer::increment_used_token(&profile, token.chars().count() as i64);
                 f(token)
             }))
         } else {
             let mut f = on_token;
             Processor::Deanonymizing(StreamingDeanonymizer::new(mapping, Box::new(move |token: String| {
-                increment_used_token(&mut conn, &apikey, token.chars().count() as i64);
+                writer::increment_used_token(&profile, token.chars().count() as i64);
                 f(token)
             })))
         };
 
         let client = Client::builder().no_proxy().build().unwrap();
-        let req = client
-            .post(config.api_url)
-            .bearer_auth(config.api_key)
-            .json(&serde_json::json!({
-                "model": config.api_model,
-                "messages": [
-                    { "role": "user", "content": anonymized_prompt }
-                ],
-                "stream": true
-            }))
-            .send()
-            .await?;
+        let mut req = client.post(&api_request.url);
+        for (name, value) in &api_request.headers {
+            req = req.header(name.as_str(), value.as_str());
+        }
+        let req = req.body(api_request.body).send().await?;
 
         info!("[AiClient::stream_api] request sent, waiting for stream response");
 
@@ -331,3 +453,21 @@ impl AiClient {
 }
 
 
+
+// 预览当前(或最近一次)聚焦输入框将要发出的请求
+#[tauri::command]
+pub async fn preview_request_command() -> Result<RequestPreview, String> {
+    let focused_input = crate::input::get_last_focused_input()
+        .ok_or_else(|| "no focused input yet".to_string())?;
+    let local_only = match crate::db::app_consent::get_consent(&focused_input.window_element.app) {
+        Some(ConsentDecision::Deny) => return Err(format!("completions are denied for {}", focused_input.window_element.app)),
+        Some(ConsentDecision::LocalOnly) => true,
+        Some(ConsentDecision::Allow) => false,
+        None if config::get_config().unwrap().privacy.require_consent => {
+            return Err(format!("no consent decision for {} yet", focused_input.window_element.app));
+        }
+        None => false,
+    };
+    let context = Context::new(&focused_input).await.ok_or("failed to build context")?;
+    Ok(AiClient::new().with_local_only(local_only).preview_request(context))
+}
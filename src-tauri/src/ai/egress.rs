@@ -0,0 +1,138 @@
+//! 外发检查: 开启仅本地模式后, 在调用提供方前拒绝任何非本机(且不在白名单中)的目标
+
+use std::net::IpAddr;
+
+use log::{info, warn};
+use reqwest::Url;
+
+use crate::config::{self, AiClientConfig};
+
+pub fn is_local_only() -> bool {
+    config::get_config().unwrap().privacy.local_only
+}
+
+// 回环地址或白名单中的主机视为本地
+pub fn is_local_url(url: &str, allow_hosts: &[String]) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']').to_lowercase(),
+        None => return false,
+    };
+    if allow_hosts.iter().any(|h| h.eq_ignore_ascii_case(&host)) {
+        return true;
+    }
+    if host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => false,
+    }
+}
+
+// 在提供方边界调用, force_local 用于按应用要求仅本地的场景
+pub fn check_egress(ai_config: &AiClientConfig, force_local: bool) -> Result<(), String> {
+    let privacy_config = config::get_config().unwrap().privacy;
+    if !privacy_config.local_only && !force_local {
+        return Ok(());
+    }
+    check_local(ai_config, &privacy_config.local_allow_hosts)
+}
+
+// 仅本地模式下目标是否允许: API 地址须为本机或白名单主机, CMD 须标记为本地
+fn check_local(ai_config: &AiClientConfig, allow_hosts: &[String]) -> Result<(), String> {
+    match ai_config.provider {
+        config::ai_client::AiProvider::API => {
+            if ai_config.api_key.is_empty() || is_local_url(&ai_config.api_url, allow_hosts) {
+                return Ok(());
+            }
+            warn!("[check_egress] refused non-local api url in local only mode");
+            Err(format!("local only mode: {} is not a local endpoint", ai_config.api_url))
+        }
+        config::ai_client::AiProvider::CMD => {
+            if ai_config.cmd_local {
+                return Ok(());
+            }
+            warn!("[check_egress] refused cmd not marked local in local only mode");
+            Err("local only mode: cmd is not marked as local (ai_client.cmd_local)".to_string())
+        }
+    }
+}
+
+pub fn set_local_only(local_only: bool) {
+    info!("[set_local_only] local only: {}", local_only);
+    if let Some(mut config) = config::get_config() {
+        config.privacy.local_only = local_only;
+        config::save_config_for_frontend(config);
+    }
+    crate::overlay::overlay::update_local_only_indicator(local_only);
+}
+
+#[tauri::command]
+pub fn get_local_only_command() -> bool {
+    is_local_only()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ai_client::AiProvider;
+
+    fn ai_config(provider: AiProvider, api_url: &str, cmd_local: bool) -> AiClientConfig {
+        AiClientConfig {
+            provider,
+            api_key: "sk-test".to_string(),
+            api_url: api_url.to_string(),
+            api_model: String::new(),
+            cmd: "llm".to_string(),
+            cmd_local,
+            prompt: String::new(),
+        }
+    }
+
+    #[test]
+    fn loopback_and_localhost_are_local() {
+        assert!(is_local_url("http://127.0.0.1:11434/v1", &[]));
+        assert!(is_local_url("http://[::1]:11434/v1", &[]));
+        assert!(is_local_url("http://localhost:8080", &[]));
+        assert!(is_local_url("http://LocalHost:8080", &[]));
+        assert!(is_local_url("http://a.localhost/v1", &[]));
+    }
+
+    #[test]
+    fn allow_listed_hosts_are_local() {
+        let allow = vec!["GPU-Box.lan".to_string()];
+        assert!(is_local_url("http://gpu-box.lan:8000/v1", &allow));
+        assert!(!is_local_url("http://gpu-box.lan:8000/v1", &[]));
+        assert!(!is_local_url("http://other.lan:8000/v1", &allow));
+    }
+
+    #[test]
+    fn lookalike_hosts_are_not_local() {
+        assert!(!is_local_url("http://localhost.evil.com/v1", &[]));
+        assert!(!is_local_url("http://localhost@evil.com/v1", &[]));
+        assert!(!is_local_url("https://api.openai.com/v1", &[]));
+    }
+
+    #[test]
+    fn unparsable_urls_are_not_local() {
+        assert!(!is_local_url("", &[]));
+        assert!(!is_local_url("localhost:11434", &[]));
+        assert!(!is_local_url("not a url", &[]));
+    }
+
+    #[test]
+    fn api_provider_needs_a_local_url() {
+        assert!(check_local(&ai_config(AiProvider::API, "http://127.0.0.1:11434/v1", false), &[]).is_ok());
+        assert!(check_local(&ai_config(AiProvider::API, "https://api.openai.com/v1", false), &[]).is_err());
+    }
+
+    #[test]
+    fn cmd_provider_needs_cmd_local() {
+        assert!(check_local(&ai_config(AiProvider::CMD, "", true), &[]).is_ok());
+        assert!(check_local(&ai_config(AiProvider::CMD, "", false), &[]).is_err());
+    }
+}
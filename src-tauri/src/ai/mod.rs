@@ -1,2 +1,3 @@
 pub mod ai_client; 
-pub mod privacy;
\ No newline at end of file
+pub mod privacy;
+pub mod egress;
@@ -4,6 +4,8 @@ use regex::Regex;
 use std::sync::Mutex;
 
 use crate::config;
+use crate::db::vault;
+use crate::utils::hash::salted_hash;
 
 // A thread-safe, lazily-initialized cache for compiled regular expressions.
 static RE_CACHE: Lazy<Mutex<HashMap<String, Regex>>> = Lazy::new(|| Mutex::new(HashMap::new()));
@@ -12,6 +14,50 @@ static RE_CACHE: Lazy<Mutex<HashMap<String, Regex>>> = Lazy::new(|| Mutex::new(H
 pub struct AnonymizedData {
     pub text: String,
     pub mapping: HashMap<String, String>,
+    pub detections: Vec<Detection>,
+}
+
+// One replaced value: the rule category that matched it and a salted hash of the original.
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub category: String,
+    pub value_hash: String,
+}
+
+fn rule_regex(rule: &str) -> Regex {
+    // Clone the regex from the cache to release the lock quickly.
+    let mut cache = RE_CACHE.lock().unwrap();
+    cache.entry(rule.to_string())
+         .or_insert_with(|| Regex::new(rule).expect("Invalid regex in config.toml"))
+         .clone()
+}
+
+// Replaces every value matched by a privacy rule with the placeholder returned by `placeholder_for(category, value)`.
+fn replace_matches<F>(text: &str, mut placeholder_for: F) -> String
+where
+    F: FnMut(&str, &str) -> String,
+{
+    let privacy_config = &config::get_config().unwrap().privacy;
+    let mut replaced_text = text.to_string();
+    for (category, rule) in &privacy_config.rules {
+        let re = rule_regex(rule);
+
+        // Collect all unique matched strings to avoid redundant processing.
+        let matches: HashSet<String> = re.find_iter(&replaced_text).map(|m| m.as_str().to_string()).collect();
+
+        for mat in matches {
+            let placeholder = placeholder_for(category, &mat);
+            // Replace all occurrences of the matched string.
+            replaced_text = replaced_text.replace(&mat, &placeholder);
+        }
+    }
+    replaced_text
+}
+
+// Returns true if any privacy rule matches the text.
+pub fn is_sensitive(text: &str) -> bool {
+    let privacy_config = &config::get_config().unwrap().privacy;
+    privacy_config.rules.values().any(|rule| rule_regex(rule).is_match(text))
 }
 
 // Anonymizes sensitive data in a given text based on regex rules in the config.
@@ -23,40 +69,50 @@ pub fn anonymize(text: &str) -> AnonymizedData {
         return AnonymizedData {
             text: text.to_string(),
             mapping: HashMap::new(),
+            detections: Vec::new(),
         };
     }
 
-    let mut anonymized_text = text.to_string();
     let mut mapping = HashMap::new();
+    let mut detections = Vec::new();
     let mut placeholder_index = 1;
 
-    for rule in &privacy_config.rules {
-        // Clone the regex from the cache to release the lock quickly.
-        let re = {
-            let mut cache = RE_CACHE.lock().unwrap();
-            cache.entry(rule.clone())
-                 .or_insert_with(|| Regex::new(rule).expect("Invalid regex in config.toml"))
-                 .clone()
-        };
-
-        // Collect all unique matched strings to avoid redundant processing.
-        let matches: HashSet<String> = re.find_iter(&anonymized_text).map(|m| m.as_str().to_string()).collect();
-
-        for mat in matches {
-            let placeholder = format!("[PRIVATE_{}]", placeholder_index);
-            // Replace all occurrences of the matched string.
-            anonymized_text = anonymized_text.replace(&mat, &placeholder);
-            mapping.insert(placeholder, mat);
-            placeholder_index += 1;
-        }
-    }
+    let anonymized_text = replace_matches(text, |category, value| {
+        let placeholder = format!("[PRIVATE_{}]", placeholder_index);
+        detections.push(Detection {
+            category: category.to_string(),
+            value_hash: salted_hash(value),
+        });
+        mapping.insert(placeholder.clone(), value.to_string());
+        placeholder_index += 1;
+        placeholder
+    });
 
     AnonymizedData {
         text: anonymized_text,
         mapping,
+        detections,
     }
 }
 
+// Redacts sensitive data before it is persisted. Values of categories listed in `vault_categories`
+// are moved into the vault behind a stable placeholder so they can be recalled later; all others
+// are dropped irreversibly.
+pub fn redact_for_storage(text: &str) -> String {
+    let privacy_config = &config::get_config().unwrap().privacy;
+    if !privacy_config.enable || !privacy_config.redact_on_store || privacy_config.rules.is_empty() {
+        return text.to_string();
+    }
+    replace_matches(text, |category, value| {
+        if privacy_config.vault_categories.iter().any(|c| c == category) {
+            if let Some(placeholder) = vault::store(category, value) {
+                return placeholder;
+            }
+        }
+        format!("[REDACTED_{}]", category)
+    })
+}
+
 // De-anonymizes text using the provided mapping.
 pub fn deanonymize(text: &str, mapping: &HashMap<String, String>) -> String {
     if mapping.is_empty() {
@@ -13,5 +13,8 @@ pub struct AiClientConfig {
     pub api_url: String,
     pub api_model: String,
     pub cmd: String,
+    // cmd 是否为本地模型, 仅本地模式下只允许本地 cmd
+    #[serde(default)]
+    pub cmd_local: bool,
     pub prompt: String,
 }
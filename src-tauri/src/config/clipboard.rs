@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    // 不记录这些应用复制的内容
+    pub ignore_apps: Vec<String>,
+    // 剪贴板记录过期时间/s, 0 表示不过期
+    pub max_age: u64,
+    // 命中隐私规则的内容直接不记录, 而不是脱敏后记录
+    pub exclude_sensitive: bool,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        ClipboardConfig {
+            ignore_apps: ["KeePass.exe", "KeePassXC.exe", "1Password.exe", "Bitwarden.exe"].map(String::from).to_vec(),
+            max_age: 3600,
+            exclude_sensitive: true,
+        }
+    }
+}
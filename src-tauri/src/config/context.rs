@@ -0,0 +1,51 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+// 输入历史在 prompt 中的序列化格式
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    // 每条历史一个 json 对象
+    Json,
+    // 每条历史一行: [应用 | 窗口标题 | 输入框标题] 内容
+    Compact,
+}
+
+// 单个上下文分区的预算
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+pub struct SectionBudget {
+    pub max_tokens: usize,
+    // 得分最高的前几条总会保留, 必要时截断
+    pub min_items: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub history_format: HistoryFormat,
+    // 每类候选历史最多取多少条参与打分
+    pub candidate_limit: i64,
+    // 时间衰减半衰期(小时)
+    pub recency_half_life_hours: f64,
+    // 当前输入框内容最多保留的 token 数(保留末尾)
+    pub surrounding_tokens: usize,
+    // 所有来源合计的 token 预算, 按来源优先级依次分配
+    pub max_tokens: usize,
+    // 按来源名(模板变量名)覆盖默认预算
+    pub budgets: IndexMap<String, SectionBudget>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        let mut budgets = IndexMap::new();
+        budgets.insert("input_history".to_string(), SectionBudget { max_tokens: 1200, min_items: 3 });
+        budgets.insert("clipboard_contents".to_string(), SectionBudget { max_tokens: 300, min_items: 1 });
+        ContextConfig {
+            history_format: HistoryFormat::Compact,
+            candidate_limit: 30,
+            recency_half_life_hours: 72.0,
+            surrounding_tokens: 500,
+            max_tokens: 2000,
+            budgets,
+        }
+    }
+}
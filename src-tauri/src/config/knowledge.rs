@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct KnowledgeConfig {
+    // 知识库目录, 为空则不启用
+    pub folder: String,
+    // 索引的文件扩展名
+    pub extensions: Vec<String>,
+    // 每个片段的最大字符数
+    pub chunk_chars: usize,
+    // 注入 prompt 的最相关片段数
+    pub top_k: usize,
+    // 检查目录变化的间隔/s
+    pub poll_interval: u64,
+}
+
+impl Default for KnowledgeConfig {
+    fn default() -> Self {
+        KnowledgeConfig {
+            folder: String::new(),
+            extensions: vec!["md".to_string(), "txt".to_string()],
+            chunk_chars: 500,
+            top_k: 3,
+            poll_interval: 10,
+        }
+    }
+}
@@ -5,6 +5,12 @@ pub mod ai_client;
 pub mod keybinding;
 pub mod privacy;
 pub mod overlay;
+pub mod context;
+pub mod storage;
+pub mod clipboard;
+pub mod knowledge;
+pub mod retention;
+pub mod stats;
 
 use log::{debug, error, info};
 pub use system::SystemConfig;
@@ -13,6 +19,12 @@ pub use ai_client::AiClientConfig;
 pub use keybinding::KeybindingConfig;
 pub use privacy::PrivacyConfig;
 pub use overlay::OverlayConfig;
+pub use context::ContextConfig;
+pub use storage::StorageConfig;
+pub use clipboard::ClipboardConfig;
+pub use knowledge::KnowledgeConfig;
+pub use retention::RetentionConfig;
+pub use stats::StatsConfig;
 
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -28,16 +40,26 @@ pub struct Config {
     pub keybinding: KeybindingConfig,
     pub privacy: PrivacyConfig,
     pub overlay: OverlayConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    #[serde(default)]
+    pub knowledge: KnowledgeConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub stats: StatsConfig,
 }
 
+// 依次查找数据目录和 exe 所在目录, 不依赖工作目录
 pub fn get_config_path() -> Option<String> {
-    let config_paths = vec!["config.toml", "src-tauri/config.toml", "../config.toml"];
-    for path in config_paths {
-        if Path::new(path).exists() {
-            return Some(path.to_string());
-        }
-    }
-    None
+    crate::utils::data_dir::config_candidates()
+        .into_iter()
+        .find(|path| path.exists())
+        .map(|path| path.to_string_lossy().to_string())
 }
 
 pub fn load_config() -> Config {
@@ -46,7 +68,7 @@ pub fn load_config() -> Config {
             .expect(format!("[load_config] Failed to read config file: {}", path).as_str());
         let config: Config = toml::from_str(&config_str)
             .expect(format!("[load_config] Failed to parse config file: {}", path).as_str());
-        info!("[load_config] load config from{} : {:?}", path, config);
+        info!("[load_config] load config from{} : {}", path, crate::utils::logger::redact_debug(&config));
         return config;
     }
     panic!("please check the config file: config.toml exists");
@@ -93,14 +115,9 @@ pub fn save_config_for_frontend(config: Config) {
         *config_guard = Some(config.clone());
     }
 
-    // 获取当前配置文件路径，如果不存在则使用默认路径
-    let config_path = get_config_path().unwrap_or_else(|| {
-        if cfg!(debug_assertions) {
-            "src-tauri/config.toml".to_string()
-        } else {
-            "config.toml".to_string()
-        }
-    });
+    // 获取当前配置文件路径，如果不存在则使用启动时会最先查找的位置
+    let data_dir_config = crate::utils::data_dir::writable_config_path().to_string_lossy().to_string();
+    let config_path = get_config_path().unwrap_or_else(|| data_dir_config.clone());
 
     // 确保目标目录存在
     if let Some(parent) = Path::new(&config_path).parent() {
@@ -116,11 +133,19 @@ pub fn save_config_for_frontend(config: Config) {
 
     match toml::to_string_pretty(&config) {
         Ok(config_str) => {
-            if let Err(e) = fs::write(&config_path, config_str) {
-                error!(
-                    "[save_config_for_frontend] Failed to write config file: {}",
-                    e
-                );
+            // 安装目录只读时改存到 writable_config_path, 下次启动最先从那里读取
+            if let Err(e) = fs::write(&config_path, &config_str) {
+                if config_path == data_dir_config {
+                    error!("[save_config_for_frontend] Failed to write config file: {}", e);
+                } else if let Err(e) = Path::new(&data_dir_config)
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| fs::write(&data_dir_config, &config_str))
+                {
+                    error!("[save_config_for_frontend] Failed to write config file: {}", e);
+                } else {
+                    info!("[save_config_for_frontend] {} is not writable, saved to {}", config_path, data_dir_config);
+                }
             }
         }
         Err(e) => {
@@ -131,3 +156,100 @@ pub fn save_config_for_frontend(config: Config) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 第一个版本的 config.toml 格式: 没有后来新增的分区和字段, privacy.rules 为正则列表
+    const LEGACY_CONFIG: &str = r#"
+[system]
+show_tray_icon = true
+start_at_login = false
+logging_level = "info"
+history_ttl = 1000
+
+[ui_automation]
+collect_interval = 10
+ignore_apps = []
+default_edit_control_types = [50004]
+hastext_edit_control_types = [50026]
+
+[ui_automation.app_edit_control_types]
+"msedge.exe" = [50003]
+
+[keyboard]
+propagation_modifier = ["LCtrl"]
+
+[keyboard.available_key]
+Tab = 9
+
+[ai_client]
+provider = "API"
+api_key = ""
+api_url = "https://example.com/v1/chat/completions"
+api_model = "model"
+cmd = ""
+prompt = "{{input_content}}"
+
+[keybinding]
+exit_overlay = ["Esc"]
+accept_candidate = ["Tab"]
+select_candidate_char_1 = ["1"]
+select_candidate_char_2 = []
+select_candidate_char_3 = []
+select_candidate_char_4 = []
+select_candidate_char_5 = []
+select_candidate_char_6 = []
+select_candidate_char_7 = []
+select_candidate_char_8 = []
+select_candidate_char_9 = []
+
+[privacy]
+enable = true
+rules = ["1[3-9]\\d{9}", "sk-[a-zA-Z0-9]{20,}"]
+
+[overlay]
+refresh_interval = 50
+relative_x = 0
+relative_y = -28
+style = ""
+"#;
+
+    #[test]
+    fn legacy_config_loads_with_defaults() {
+        let config: Config = toml::from_str(LEGACY_CONFIG).unwrap();
+        assert!(!config.system.diagnostic_logging);
+        assert_eq!(config.privacy.outbound.handles, privacy::FieldPolicy::Drop);
+        assert_eq!(config.context.history_format, context::HistoryFormat::Compact);
+        assert!(!config.privacy.local_only);
+        assert!(!config.ai_client.cmd_local);
+        assert_eq!(config.storage.encryption, storage::Encryption::None);
+        assert!(config.privacy.require_consent);
+        assert!(!config.privacy.redact_on_store);
+        assert_eq!(config.privacy.vault_keyfile, "vault.key");
+        assert_eq!(config.clipboard.max_age, 3600);
+        assert_eq!(config.context.candidate_limit, 30);
+        assert_eq!(config.context.budgets.len(), 2);
+        assert!(config.knowledge.folder.is_empty());
+        assert_eq!(config.system.history_draft_window, 300);
+        assert_eq!(config.ui_automation.nearby_max_items, 40);
+        assert_eq!(config.privacy.outbound.screen_context, privacy::FieldPolicy::Include);
+        assert_eq!(config.retention.purge_interval, 3600);
+        assert_eq!(config.stats.summary_interval, 0);
+    }
+
+    #[test]
+    fn legacy_rule_list_gets_category_names() {
+        let config: Config = toml::from_str(LEGACY_CONFIG).unwrap();
+        let categories: Vec<&str> = config.privacy.rules.keys().map(String::as_str).collect();
+        assert_eq!(categories, vec!["rule_1", "rule_2"]);
+        assert_eq!(config.privacy.rules["rule_2"], "sk-[a-zA-Z0-9]{20,}");
+    }
+
+    #[test]
+    fn shipped_config_parses() {
+        let config: Config = toml::from_str(include_str!("../../config.toml")).unwrap();
+        assert!(config.privacy.rules.contains_key("phone"));
+    }
+}
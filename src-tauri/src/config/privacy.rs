@@ -1,7 +1,91 @@
-use serde::{Deserialize, Serialize};
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PrivacyConfig {
     pub enable: bool,
-    pub rules: Vec<String>,
-} 
\ No newline at end of file
+    // 仅本地模式: 拒绝向非本机(且不在白名单中)的目标发送任何内容
+    #[serde(default)]
+    pub local_only: bool,
+    #[serde(default)]
+    pub local_allow_hosts: Vec<String>,
+    // 应用首次触发补全时询问是否允许
+    #[serde(default = "default_require_consent")]
+    pub require_consent: bool,
+    // 输入历史和剪贴板在保存前脱敏
+    #[serde(default)]
+    pub redact_on_store: bool,
+    // 保存前脱敏时, 这些类别的原值存入保险库以便之后还原, 其它类别直接丢弃
+    #[serde(default = "default_vault_categories")]
+    pub vault_categories: Vec<String>,
+    #[serde(default = "default_vault_keyfile")]
+    pub vault_keyfile: String,
+    // 检测类别 -> 正则; 也接受旧版本的正则列表
+    #[serde(deserialize_with = "deserialize_rules")]
+    pub rules: IndexMap<String, String>,
+    #[serde(default)]
+    pub outbound: OutboundPolicyConfig,
+}
+
+fn default_require_consent() -> bool {
+    true
+}
+
+fn default_vault_categories() -> Vec<String> {
+    vec!["phone".to_string()]
+}
+
+fn default_vault_keyfile() -> String {
+    "vault.key".to_string()
+}
+
+// 字段外发策略: 原样发送, 发送哈希, 不发送
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum FieldPolicy {
+    Include,
+    Hash,
+    Drop,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct OutboundPolicyConfig {
+    pub app_name: FieldPolicy,
+    pub window_title: FieldPolicy,
+    // 窗口和输入框句柄
+    pub handles: FieldPolicy,
+    pub input_title: FieldPolicy,
+    pub history: FieldPolicy,
+    pub clipboard: FieldPolicy,
+    // 输入框周围的界面文本
+    pub screen_context: FieldPolicy,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RulesFormat {
+    Map(IndexMap<String, String>),
+    // 旧版本: rules = ["正则", ...], 没有类别名
+    List(Vec<String>),
+}
+
+fn deserialize_rules<'de, D: Deserializer<'de>>(deserializer: D) -> Result<IndexMap<String, String>, D::Error> {
+    Ok(match RulesFormat::deserialize(deserializer)? {
+        RulesFormat::Map(rules) => rules,
+        RulesFormat::List(rules) => rules.into_iter().enumerate().map(|(i, rule)| (format!("rule_{}", i + 1), rule)).collect(),
+    })
+}
+
+impl Default for OutboundPolicyConfig {
+    fn default() -> Self {
+        OutboundPolicyConfig {
+            app_name: FieldPolicy::Include,
+            window_title: FieldPolicy::Include,
+            handles: FieldPolicy::Drop,
+            input_title: FieldPolicy::Include,
+            history: FieldPolicy::Include,
+            clipboard: FieldPolicy::Include,
+            screen_context: FieldPolicy::Include,
+        }
+    }
+}
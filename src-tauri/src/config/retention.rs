@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+// 按应用和窗口标题匹配的保留规则, 按顺序取第一条命中的规则
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RetentionRule {
+    // 应用名, 为空匹配所有应用
+    #[serde(default)]
+    pub app: String,
+    // 窗口标题正则, 为空匹配所有窗口
+    #[serde(default)]
+    pub title_pattern: String,
+    // 保留天数, 0 表示不过期
+    pub ttl_days: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct RetentionConfig {
+    // 最多保留的历史条数(不含置顶), 0 表示不限制
+    pub max_rows: u64,
+    // 输入历史(input 表各行, 不含索引和其它表)的最大体积/MB, 超出时删除最早的历史, 0 表示不限制
+    pub max_size_mb: u64,
+    // 不保存这些应用的输入历史
+    pub never_store_apps: Vec<String>,
+    // 不保存窗口标题匹配这些正则的输入历史
+    pub never_store_titles: Vec<String>,
+    // 后台清理间隔/s
+    pub purge_interval: u64,
+    // 整理数据库文件(VACUUM)的间隔/h, 0 表示不整理
+    pub vacuum_interval: u64,
+    pub rules: Vec<RetentionRule>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            max_rows: 0,
+            max_size_mb: 0,
+            never_store_apps: Vec::new(),
+            never_store_titles: vec!["InPrivate".to_string(), "Incognito".to_string()],
+            purge_interval: 3600,
+            vacuum_interval: 168,
+            rules: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct StatsConfig {
+    // 定期发送使用摘要通知的间隔/h, 统计范围为最近一个间隔, 0 表示不发送
+    pub summary_interval: u64,
+}
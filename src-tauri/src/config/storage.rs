@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+// input.db 加密方式
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    None,
+    // 随机密钥保存在 keyfile 中
+    Keyfile,
+    // 密钥由环境变量中的口令派生
+    Passphrase,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct StorageConfig {
+    // 数据目录, 为空时使用 %APPDATA%\ainput; 命令行 --data-dir 和环境变量 AINPUT_DATA_DIR 优先
+    pub data_dir: String,
+    pub encryption: Encryption,
+    pub keyfile: String,
+    pub passphrase_env: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            data_dir: String::new(),
+            encryption: Encryption::None,
+            keyfile: "input.key".to_string(),
+            passphrase_env: "AINPUT_DB_PASSPHRASE".to_string(),
+        }
+    }
+}
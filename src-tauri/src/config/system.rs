@@ -5,5 +5,15 @@ pub struct SystemConfig {
     pub show_tray_icon: bool,
     pub start_at_login: bool,
     pub logging_level: String,
+    // 诊断日志: 在日志中输出输入内容、窗口标题、prompt 等原文
+    #[serde(default)]
+    pub diagnostic_logging: bool,
     pub history_ttl: u64,
+    // 同一输入框间隔不超过该秒数的草稿快照合并为一条, 0 表示不合并
+    #[serde(default = "default_history_draft_window")]
+    pub history_draft_window: u64,
+}
+
+fn default_history_draft_window() -> u64 {
+    300
 }
@@ -9,4 +9,32 @@ pub struct UiAutomationConfig {
     pub default_edit_control_types: Vec<i32>,
     pub hastext_edit_control_types: Vec<i32>,
     pub app_edit_control_types: HashMap<String, Vec<i32>>,
+    // 输入框周围文本(screen_context)最多收集的条数
+    #[serde(default = "default_nearby_max_items")]
+    pub nearby_max_items: usize,
+    // 输入框周围文本合计的最大字符数
+    #[serde(default = "default_nearby_max_chars")]
+    pub nearby_max_chars: usize,
+    // 向上取几层父元素作为面板收集文本
+    #[serde(default = "default_nearby_pane_levels")]
+    pub nearby_pane_levels: usize,
+    // 面板子树最多访问的元素数
+    #[serde(default = "default_nearby_max_nodes")]
+    pub nearby_max_nodes: usize,
+}
+
+fn default_nearby_max_items() -> usize {
+    40
+}
+
+fn default_nearby_max_chars() -> usize {
+    2000
+}
+
+fn default_nearby_pane_levels() -> usize {
+    2
+}
+
+fn default_nearby_max_nodes() -> usize {
+    300
 }
@@ -0,0 +1,397 @@
+//! 给候选上下文打分, 再按每个分区的 token 预算取舍
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::config::{self, context::SectionBudget};
+use crate::context::history::search::{is_cjk, tokenize};
+use crate::context::source::{FetchedSource, SourceItem};
+use crate::context::SourceContext;
+use crate::os::element::ui_automation::FocusedInput;
+
+const W_SAME_FIELD: f64 = 3.0;
+const W_SAME_WINDOW: f64 = 1.5;
+const W_SAME_APP: f64 = 1.0;
+const W_SIMILARITY: f64 = 3.0;
+const W_RECENCY: f64 = 2.0;
+const W_ACCEPTED: f64 = 1.0;
+
+// 最近被采纳的候选, 用于给包含这些内容的历史加分
+const ACCEPTED_LIMIT: usize = 100;
+static ACCEPTED: Lazy<Mutex<VecDeque<String>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+
+pub fn record_acceptance(text: &str) {
+    let text = text.trim();
+    if text.chars().count() < 2 {
+        return;
+    }
+    let mut accepted = ACCEPTED.lock().unwrap();
+    accepted.push_back(text.to_string());
+    while accepted.len() > ACCEPTED_LIMIT {
+        accepted.pop_front();
+    }
+}
+
+fn is_accepted(content: &str) -> bool {
+    ACCEPTED.lock().unwrap().iter().any(|a| content.contains(a.as_str()))
+}
+
+// 粗略估算: CJK 每字 1 token, 其它约 4 字符 1 token
+pub fn estimate_tokens(text: &str) -> usize {
+    let (cjk, other) = text.chars().fold((0, 0), |(cjk, other), c| {
+        if is_cjk(c) { (cjk + 1, other) } else { (cjk, other + 1) }
+    });
+    cjk + (other + 3) / 4
+}
+
+// 保留末尾不超过 max_tokens 的部分
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    if estimate_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let (mut cjk, mut other) = (0, 0);
+    let mut start = chars.len();
+    while start > 0 {
+        let c = chars[start - 1];
+        let (next_cjk, next_other) = if is_cjk(c) { (cjk + 1, other) } else { (cjk, other + 1) };
+        if next_cjk + (next_other + 3) / 4 > max_tokens {
+            break;
+        }
+        (cjk, other) = (next_cjk, next_other);
+        start -= 1;
+    }
+    chars[start..].iter().collect()
+}
+
+fn similarity(query: &HashSet<String>, text: &str) -> f64 {
+    if query.is_empty() {
+        return 0.0;
+    }
+    let terms: HashSet<String> = tokenize(text, false).into_iter().map(|t| t.text).collect();
+    let common = query.intersection(&terms).count();
+    if common == 0 {
+        return 0.0;
+    }
+    common as f64 / query.union(&terms).count() as f64
+}
+
+fn recency(timestamp: i64, now: i64, half_life_hours: f64) -> f64 {
+    let age_hours = (now - timestamp).max(0) as f64 / 3_600_000.0;
+    (-std::f64::consts::LN_2 * age_hours / half_life_hours.max(0.1)).exp()
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Signals {
+    pub same_field: bool,
+    pub same_window: bool,
+    pub same_app: bool,
+    pub similarity: f64,
+    pub recency: f64,
+    pub accepted: bool,
+    pub pinned: bool,
+}
+
+impl Signals {
+    fn score(&self) -> f64 {
+        let flag = |b: bool, w: f64| if b { w } else { 0.0 };
+        flag(self.same_field, W_SAME_FIELD)
+            + flag(self.same_window, W_SAME_WINDOW)
+            + flag(self.same_app, W_SAME_APP)
+            + flag(self.accepted, W_ACCEPTED)
+            + self.similarity * W_SIMILARITY
+            + self.recency * W_RECENCY
+    }
+}
+
+/// 一条候选的打分和取舍结果, 不含原文, 可直接用于调试展示
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoredItem {
+    pub section: &'static str,
+    pub id: String,
+    pub score: f64,
+    pub signals: Signals,
+    pub tokens: usize,
+    pub included: bool,
+    pub truncated: bool,
+}
+
+pub struct Assembled {
+    pub input_content: String,
+    pub sources: Vec<SourceContext>,
+    pub items: Vec<ScoredItem>,
+}
+
+fn score_item(item: &SourceItem, app: &FocusedInput, query: &HashSet<String>, now: i64, half_life_hours: f64) -> Signals {
+    let (same_field, same_window, same_app) = match &item.origin {
+        Some(origin) => {
+            let same_window = (!origin.window_id.is_empty() && origin.window_id == app.window_element.id.to_string())
+                || (!origin.window_title.is_empty() && origin.window_title == app.window_element.title);
+            let same_field = same_window
+                && ((!origin.input_id.is_empty() && origin.input_id == app.input_element.id)
+                    || (!origin.input_title.is_empty() && origin.input_title == app.input_element.text));
+            (same_field, same_window, origin.window_app == app.window_element.app)
+        }
+        None => (false, false, false),
+    };
+    Signals {
+        same_field,
+        same_window,
+        same_app,
+        similarity: similarity(query, &item.text),
+        recency: item.timestamp.map(|t| recency(t, now, half_life_hours)).unwrap_or(0.0),
+        accepted: is_accepted(&item.text),
+        pinned: item.pinned,
+    }
+}
+
+fn item_tokens(item: &SourceItem) -> usize {
+    let label_tokens = item.origin.as_ref().map_or(0, |o| {
+        estimate_tokens(&o.window_app) + estimate_tokens(&o.window_title) + estimate_tokens(&o.input_title)
+    });
+    estimate_tokens(&item.text) + label_tokens
+}
+
+// 置顶条目在前, 其余按得分从高到低填充: 置顶条目和前 min_items 条优先保留(平分预算截断), 其余放得下才保留
+// 总量不超过分区预算, 预算用完后置顶条目也不再保留
+fn fill(name: &'static str, mut candidates: Vec<(SourceItem, Signals)>, budget: SectionBudget) -> (Vec<SourceItem>, Vec<ScoredItem>, usize) {
+    candidates.sort_by(|a, b| b.1.pinned.cmp(&a.1.pinned).then(b.1.score().total_cmp(&a.1.score())));
+    let reserved_cap = budget.max_tokens / budget.min_items.max(1);
+    let mut used = 0;
+    let mut selected = Vec::new();
+    let mut items = Vec::new();
+    for (rank, (mut item, signals)) in candidates.into_iter().enumerate() {
+        let mut scored = ScoredItem {
+            section: name,
+            id: item.id.clone(),
+            score: signals.score(),
+            signals,
+            tokens: item_tokens(&item),
+            included: false,
+            truncated: false,
+        };
+        if rank < budget.min_items || scored.signals.pinned {
+            // 来源标签不截断, 只截断正文
+            let cap = reserved_cap.min(budget.max_tokens - used);
+            let label_tokens = scored.tokens - estimate_tokens(&item.text);
+            if cap <= label_tokens {
+                items.push(scored);
+                continue;
+            }
+            if scored.tokens > cap {
+                item.text = truncate_to_tokens(&item.text, cap - label_tokens);
+                scored.truncated = true;
+                scored.tokens = item_tokens(&item);
+            }
+        } else if used + scored.tokens > budget.max_tokens {
+            items.push(scored);
+            continue;
+        }
+        used += scored.tokens;
+        scored.included = true;
+        items.push(scored);
+        selected.push(item);
+    }
+    (selected, items, used)
+}
+
+// 来源已按优先级排序, 依次在各自预算和剩余总预算内取舍
+pub fn assemble(app: &FocusedInput, fetched: Vec<FetchedSource>) -> Assembled {
+    let config = config::get_config().unwrap().context;
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
+
+    let input_content = truncate_to_tokens(&app.input_element.content, config.surrounding_tokens);
+    let query: HashSet<String> = tokenize(&input_content, false).into_iter().map(|t| t.text).collect();
+
+    let mut remaining = config.max_tokens;
+    let mut sources = Vec::new();
+    let mut items = Vec::new();
+    for FetchedSource { source, items: candidates } in fetched {
+        let name = source.name();
+        let budget = config.budgets.get(name).copied().or_else(|| source.budget_hint());
+        let selected = match budget {
+            Some(budget) => {
+                let budget = SectionBudget { max_tokens: budget.max_tokens.min(remaining), ..budget };
+                let candidates = candidates
+                    .into_iter()
+                    .filter(|c| !c.text.trim().is_empty())
+                    .map(|c| {
+                        let signals = score_item(&c, app, &query, now, config.recency_half_life_hours);
+                        (c, signals)
+                    })
+                    .collect();
+                let (selected, scored, used) = fill(name, candidates, budget);
+                remaining = remaining.saturating_sub(used);
+                items.extend(scored);
+                selected
+            }
+            None => candidates,
+        };
+        sources.push(SourceContext {
+            name,
+            rendered: source.render(&selected),
+            items: selected,
+        });
+    }
+
+    Assembled { input_content, sources, items }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::InputContext;
+    use crate::os::element::ui_automation::UIElement;
+    use crate::os::WindowElement;
+
+    const HOUR: i64 = 3_600_000;
+
+    fn focused() -> FocusedInput {
+        FocusedInput {
+            window_element: WindowElement {
+                id: 1,
+                app: "chat.exe".to_string(),
+                x: 0,
+                y: 0,
+                width: 800,
+                height: 600,
+                title: "Bob".to_string(),
+                class_name: String::new(),
+            },
+            input_element: UIElement {
+                id: "input".to_string(),
+                text: "Message".to_string(),
+                x: 0,
+                y: 0,
+                z: 0,
+                width: 800,
+                height: 40,
+                window_id: 1,
+                control_type: 50004,
+                element_type: 0,
+                content: String::new(),
+            },
+            nearby: Vec::new(),
+        }
+    }
+
+    fn from_origin(window_id: &str, window_app: &str, window_title: &str, input_id: &str) -> SourceItem {
+        let origin = InputContext {
+            window_id: window_id.to_string(),
+            window_app: window_app.to_string(),
+            window_title: window_title.to_string(),
+            input_id: input_id.to_string(),
+            ..Default::default()
+        };
+        SourceItem { id: input_id.to_string(), text: "see you".to_string(), timestamp: None, origin: Some(origin), pinned: false, nearby_kind: None }
+    }
+
+    fn query(text: &str) -> HashSet<String> {
+        tokenize(text, false).into_iter().map(|t| t.text).collect()
+    }
+
+    #[test]
+    fn similarity_is_jaccard_of_terms() {
+        assert_eq!(similarity(&HashSet::new(), "hello world"), 0.0);
+        assert_eq!(similarity(&query("hello world"), "good morning"), 0.0);
+        assert_eq!(similarity(&query("hello world"), "Hello World"), 1.0);
+        assert!((similarity(&query("hello world"), "hello there") - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recency_halves_every_half_life() {
+        let now = 100 * HOUR;
+        assert_eq!(recency(now, now, 24.0), 1.0);
+        assert!((recency(now - 24 * HOUR, now, 24.0) - 0.5).abs() < 1e-9);
+        assert!((recency(now - 48 * HOUR, now, 24.0) - 0.25).abs() < 1e-9);
+        // 时钟回拨产生的未来时间按刚刚处理
+        assert_eq!(recency(now + HOUR, now, 24.0), 1.0);
+    }
+
+    #[test]
+    fn score_prefers_same_field_then_window_then_app() {
+        let app = focused();
+        let items = [
+            from_origin("1", "chat.exe", "Bob", "input"),
+            from_origin("1", "chat.exe", "Bob", "search"),
+            from_origin("2", "chat.exe", "Alice", "input"),
+            from_origin("3", "mail.exe", "Inbox", "input"),
+        ];
+        let scores: Vec<f64> = items.iter().map(|i| score_item(i, &app, &HashSet::new(), 0, 24.0).score()).collect();
+        assert!(scores.windows(2).all(|w| w[0] > w[1]), "{:?}", scores);
+        assert_eq!(scores[3], 0.0);
+    }
+
+    #[test]
+    fn score_rewards_similar_and_recent_items() {
+        let app = focused();
+        let now = 100 * HOUR;
+        let mut old = from_origin("3", "mail.exe", "Inbox", "input");
+        old.timestamp = Some(now - 72 * HOUR);
+        let mut recent = old.clone();
+        recent.timestamp = Some(now - HOUR);
+        let mut similar = old.clone();
+        similar.text = "see you at noon".to_string();
+
+        let score = |item: &SourceItem| score_item(item, &app, &query("noon"), now, 24.0).score();
+        assert!(score(&recent) > score(&old));
+        assert!(score(&similar) > score(&old));
+    }
+
+    #[test]
+    fn min_items_are_truncated_to_an_equal_share() {
+        let text = format!("{}{}", "a".repeat(240), "b".repeat(160));
+        let candidates = (0..4).map(|i| candidate(i, &text, false)).collect();
+        let (selected, items, used) = fill("history", candidates, SectionBudget { max_tokens: 120, min_items: 3 });
+        // 每条 100 token, 前 3 条各截断为 40 token, 保留末尾; 第 4 条放不下
+        assert_eq!(used, 120);
+        assert_eq!(selected.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), ["0", "1", "2"]);
+        assert!(selected.iter().all(|s| s.text == "b".repeat(160)));
+        assert!(items[..3].iter().all(|i| i.included && i.truncated && i.tokens == 40));
+        assert!(!items[3].included);
+    }
+
+    #[test]
+    fn items_within_their_share_are_not_truncated() {
+        let candidates = vec![candidate(0, &"a".repeat(40), false), candidate(1, &"b".repeat(400), false)];
+        let (selected, items, used) = fill("history", candidates, SectionBudget { max_tokens: 120, min_items: 2 });
+        assert_eq!(selected[0].text, "a".repeat(40));
+        assert!(!items[0].truncated);
+        assert!(items[1].truncated);
+        assert_eq!(used, 10 + 60);
+    }
+
+    fn candidate(id: usize, text: &str, pinned: bool) -> (SourceItem, Signals) {
+        let item = SourceItem { id: id.to_string(), text: text.to_string(), timestamp: None, origin: None, pinned, nearby_kind: None };
+        let signals = Signals { pinned, recency: 1.0 / (id + 1) as f64, ..Default::default() };
+        (item, signals)
+    }
+
+    #[test]
+    fn pinned_items_stay_within_budget() {
+        let text = "x".repeat(400);
+        let candidates = (0..10).map(|i| candidate(i, &text, true)).collect();
+        let (selected, items, used) = fill("history", candidates, SectionBudget { max_tokens: 120, min_items: 2 });
+        assert!(used <= 120);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(items.iter().filter(|i| i.included).count(), 2);
+        assert!(items.iter().filter(|i| i.included).all(|i| i.truncated));
+    }
+
+    #[test]
+    fn pinned_items_come_first_and_rest_fill_remaining_budget() {
+        let candidates = vec![
+            candidate(0, &"a".repeat(40), false),
+            candidate(1, &"b".repeat(40), true),
+            candidate(2, &"c".repeat(40), false),
+            candidate(3, &"d".repeat(400), false),
+        ];
+        let (selected, _, used) = fill("history", candidates, SectionBudget { max_tokens: 40, min_items: 1 });
+        let ids: Vec<&str> = selected.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["1", "0", "2"]);
+        assert_eq!(used, 30);
+    }
+}
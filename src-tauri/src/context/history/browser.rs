@@ -0,0 +1,195 @@
+//! 历史管理: 分页浏览、编辑、置顶和删除, 供设置界面清理会作为上下文的内容
+
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::privacy::redact_for_storage;
+use crate::db::conn::with_conn;
+use crate::db::input_history::{input, Input};
+
+use super::search;
+
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HistoryFilter {
+    pub app: Option<String>,
+    pub window: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    // 按内容子串过滤
+    pub text: Option<String>,
+    pub pinned_only: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HistoryPage {
+    pub items: Vec<Input>,
+    pub total: i64,
+    pub page: i64,
+    pub page_size: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AppHistoryCount {
+    pub app: String,
+    pub count: i64,
+}
+
+// LIKE 中 % 和 _ 是通配符, 按字面匹配时需转义, 查询时配合 .escape('\\')
+pub fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn filtered(filter: &HistoryFilter) -> input::BoxedQuery<'static, Sqlite> {
+    use crate::db::input_history::input::dsl::*;
+    let mut query = input.into_boxed();
+    if let Some(app) = filter.app.clone().filter(|a| !a.is_empty()) {
+        query = query.filter(window_app.eq(app));
+    }
+    if let Some(window) = filter.window.clone().filter(|w| !w.is_empty()) {
+        query = query.filter(window_title.eq(window));
+    }
+    if let Some(from) = filter.from {
+        query = query.filter(timestamp.ge(from));
+    }
+    if let Some(to) = filter.to {
+        query = query.filter(timestamp.lt(to));
+    }
+    if let Some(text) = filter.text.as_ref().filter(|t| !t.trim().is_empty()) {
+        query = query.filter(input_content.like(format!("%{}%", escape_like(text.trim()))).escape('\\'));
+    }
+    if filter.pinned_only {
+        query = query.filter(pinned.eq(true));
+    }
+    query
+}
+
+// page 从 0 开始, 按时间倒序
+pub fn list_history(conn: &mut SqliteConnection, filter: &HistoryFilter, page: i64, page_size: i64) -> QueryResult<HistoryPage> {
+    use crate::db::input_history::input::dsl::*;
+    let page = page.max(0);
+    let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+    let total = filtered(filter).count().get_result::<i64>(conn)?;
+    let items = filtered(filter)
+        .order(timestamp.desc())
+        .offset(page * page_size)
+        .limit(page_size)
+        .load::<Input>(conn)?;
+    Ok(HistoryPage { items, total, page, page_size })
+}
+
+pub fn history_apps(conn: &mut SqliteConnection) -> QueryResult<Vec<AppHistoryCount>> {
+    use crate::db::input_history::input::dsl::*;
+    let rows = input
+        .group_by(window_app)
+        .select((window_app, diesel::dsl::count_star()))
+        .order(diesel::dsl::count_star().desc())
+        .load::<(String, i64)>(conn)?;
+    Ok(rows.into_iter().map(|(app, count)| AppHistoryCount { app, count }).collect())
+}
+
+pub fn delete_history(conn: &mut SqliteConnection, ids: &[String]) -> QueryResult<usize> {
+    use crate::db::input_history::input::dsl::*;
+    let deleted = conn.transaction(|conn| {
+        let mut deleted = 0;
+        for chunk in ids.chunks(500) {
+            deleted += diesel::delete(input.filter(id.eq_any(chunk))).execute(conn)?;
+        }
+        Ok::<usize, diesel::result::Error>(deleted)
+    })?;
+    search::remove_records(ids);
+    Ok(deleted)
+}
+
+pub fn delete_app_history(conn: &mut SqliteConnection, app: &str) -> QueryResult<usize> {
+    use crate::db::input_history::input::dsl::*;
+    let ids = input.filter(window_app.eq(app)).select(id).load::<String>(conn)?;
+    delete_history(conn, &ids)
+}
+
+pub fn update_history(conn: &mut SqliteConnection, record_id: &str, content: &str) -> QueryResult<Input> {
+    use crate::db::input_history::input::dsl::*;
+    diesel::update(input.filter(id.eq(record_id)))
+        .set(input_content.eq(redact_for_storage(content)))
+        .execute(conn)?;
+    let record = input.filter(id.eq(record_id)).first::<Input>(conn)?;
+    search::index_record(&record);
+    Ok(record)
+}
+
+pub fn pin_history(conn: &mut SqliteConnection, record_id: &str, value: bool) -> QueryResult<usize> {
+    use crate::db::input_history::input::dsl::*;
+    diesel::update(input.filter(id.eq(record_id))).set(pinned.eq(value)).execute(conn)
+}
+
+#[tauri::command]
+pub async fn list_history_command(filter: Option<HistoryFilter>, page: Option<i64>, page_size: Option<i64>) -> Result<HistoryPage, String> {
+    let filter = filter.unwrap_or_default();
+    with_conn(move |conn| list_history(conn, &filter, page.unwrap_or(0), page_size.unwrap_or(50)))
+        .await?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_history_apps_command() -> Result<Vec<AppHistoryCount>, String> {
+    with_conn(history_apps).await?.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_history_command(ids: Vec<String>) -> Result<usize, String> {
+    let deleted = with_conn(move |conn| delete_history(conn, &ids))
+        .await?
+        .map_err(|e| e.to_string())?;
+    info!("[delete_history_command] deleted {} history entries", deleted);
+    Ok(deleted)
+}
+
+#[tauri::command]
+pub async fn delete_app_history_command(app: String) -> Result<usize, String> {
+    let deleted = with_conn(move |conn| delete_app_history(conn, &app))
+        .await?
+        .map_err(|e| e.to_string())?;
+    info!("[delete_app_history_command] deleted {} history entries", deleted);
+    Ok(deleted)
+}
+
+#[tauri::command]
+pub async fn update_history_command(id: String, content: String) -> Result<Input, String> {
+    with_conn(move |conn| update_history(conn, &id, &content))
+        .await?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn pin_history_command(id: String, pinned: bool) -> Result<(), String> {
+    let updated = with_conn(move |conn| pin_history(conn, &id, pinned))
+        .await?
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("history entry not found".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_like;
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("100%"), "100\\%");
+        assert_eq!(escape_like("a_b\\c"), "a\\_b\\\\c");
+        assert_eq!(escape_like("你好"), "你好");
+    }
+}
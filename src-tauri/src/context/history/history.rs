@@ -1,4 +1,7 @@
+use crate::ai::privacy::redact_for_storage;
 use crate::db::input_history::{Input, insert_history};
+use super::browser::escape_like;
+use super::search;
 use std::collections::HashSet;
 use diesel::prelude::*;
 
@@ -20,24 +23,58 @@ pub struct InputHistory {
     pub input_height: i32,
     pub input_content: String,
     pub timestamp: i64,
+    // 输入框在这条快照之前被清空过(已发送), 不作为上一条的草稿
+    pub new_message: bool,
 }
 
-pub fn save_history(conn: &mut SqliteConnection, input_history: &InputHistory) {
+// 新快照是否为旧快照的后续草稿: 在旧内容后继续输入或退格删除末尾, 或长度相近且首尾相同部分占较长者的大部分(中间小幅修改)
+// 发送后清空再输入由调用方通过 InputHistory::new_message 区分, 不经过这里
+fn is_draft_of(old: &str, new: &str) -> bool {
+    if new.starts_with(old) || old.starts_with(new) {
+        return true;
+    }
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+    if new_chars.len() * 10 < old_chars.len() * 8 {
+        return false;
+    }
+    let prefix = old_chars.iter().zip(&new_chars).take_while(|(a, b)| a == b).count();
+    let suffix = old_chars[prefix..].iter().rev().zip(new_chars[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let longest = old_chars.len().max(new_chars.len());
+    (prefix + suffix) * 10 >= longest * 8
+}
+
+// 同一窗口的同一输入框(句柄或标题相同)
+fn is_same_field(a: &Input, b: &Input) -> bool {
+    a.window_app == b.window_app
+        && ((a.window_id == b.window_id && a.input_id == b.input_id)
+            || (a.window_title == b.window_title && a.input_title == b.input_title))
+}
+
+// 返回写入的记录, 由调用方在事务提交后加入搜索索引
+pub fn save_history(conn: &mut SqliteConnection, input_history: &InputHistory) -> Option<Input> {
     if input_history.input_content.trim().is_empty() {
-        return;
+        return None;
+    }
+    if super::retention::never_store(&input_history.window_app, &input_history.window_title) {
+        return None;
+    }
+    // 写线程已持有连接和事务, 在同一连接上查询, 不再从连接池取
+    match crate::db::app_consent::denied_apps(conn) {
+        Ok(denied) if denied.contains(&input_history.window_app) => return None,
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("[save_history] failed to read app consents: {}", e);
+            return None;
+        }
     }
     use crate::db::input_history::input::dsl::*;
-    // 查询最后一条记录
-    let last: Option<Input> = input
-        .order(timestamp.desc())
-        .first::<Input>(conn)
-        .ok();
     // 将 InputHistory 转换为 Input（只填充有的字段，id 用当前时间戳字符串）
-    let new_input = Input {
+    let mut new_input = Input {
         id: format!("{}", input_history.timestamp),
         window_id: input_history.window_id.to_string(),
         window_app: input_history.window_app.clone(),
-        window_title: input_history.window_title.clone(),
+        window_title: redact_for_storage(&input_history.window_title),
         window_class_name: input_history.window_class_name.clone(),
         window_x: input_history.window_x,
         window_y: input_history.window_y,
@@ -50,23 +87,85 @@ pub fn save_history(conn: &mut SqliteConnection, input_history: &InputHistory) {
         input_y: input_history.input_y,
         input_width: input_history.input_width,
         input_height: input_history.input_height,
-        input_content: input_history.input_content.clone(),
+        input_content: redact_for_storage(&input_history.input_content),
         timestamp: input_history.timestamp,
+        pinned: false,
     };
-    let is_same = if let Some(last) = last {
-        last.window_id == new_input.window_id &&
-        last.window_app == new_input.window_app &&
-        last.input_id == new_input.input_id &&
-        last.input_title == new_input.input_title &&
-        last.input_content == new_input.input_content
-    } else {
-        false
-    };
-    if !is_same {
-        insert_history(conn, &new_input);
+    // 查询同一输入框的最后一条记录 (idx_input_app)
+    let last: Option<Input> = input
+        .filter(window_app.eq(&new_input.window_app))
+        .filter(
+            window_id.eq(&new_input.window_id).and(input_id.eq(&new_input.input_id))
+                .or(window_title.eq(&new_input.window_title).and(input_title.eq(&new_input.input_title))),
+        )
+        .order(timestamp.desc())
+        .first::<Input>(conn)
+        .ok();
+    if let Some(last) = last {
+        if last.input_content == new_input.input_content {
+            return None;
+        }
+        // 同一会话内的草稿快照覆盖上一条, 只保留最终内容
+        let draft_window = crate::config::get_config().unwrap().system.history_draft_window as i64 * 1000;
+        if !input_history.new_message
+            && new_input.timestamp - last.timestamp <= draft_window
+            && is_draft_of(&last.input_content, &new_input.input_content)
+        {
+            new_input.id = last.id;
+            new_input.pinned = last.pinned;
+        }
+    }
+    insert_history(conn, &new_input).then_some(new_input)
+}
+
+// 按时间升序的历史中, 被同一输入框后续草稿取代的记录 id
+// 已保存的记录没有清空标记, 会话内缩短为前缀的快照也按草稿合并
+fn superseded_drafts(rows: Vec<Input>, draft_window: i64) -> Vec<String> {
+    let mut removed = Vec::new();
+    // 各输入框仍在会话内的最后一条
+    let mut open: Vec<Input> = Vec::new();
+    for row in rows {
+        open.retain(|o| row.timestamp - o.timestamp <= draft_window);
+        match open.iter().position(|o| is_same_field(o, &row)) {
+            Some(pos) => {
+                if !open[pos].pinned && is_draft_of(&open[pos].input_content, &row.input_content) {
+                    removed.push(open[pos].id.clone());
+                }
+                open[pos] = row;
+            }
+            None => open.push(row),
+        }
     }
+    removed
 }
 
+// 合并已有数据库中的草稿快照, 返回删除的条数
+pub fn compact_history(conn: &mut SqliteConnection) -> QueryResult<usize> {
+    use crate::db::input_history::input::dsl::*;
+    let draft_window = crate::config::get_config().unwrap().system.history_draft_window as i64 * 1000;
+    if draft_window <= 0 {
+        return Ok(0);
+    }
+    let rows = input.order(timestamp.asc()).load::<Input>(conn)?;
+    let removed = superseded_drafts(rows, draft_window);
+    conn.transaction(|conn| {
+        for ids in removed.chunks(500) {
+            diesel::delete(input.filter(id.eq_any(ids))).execute(conn)?;
+        }
+        Ok::<(), diesel::result::Error>(())
+    })?;
+    search::remove_records(&removed);
+    Ok(removed.len())
+}
+
+#[tauri::command]
+pub async fn compact_history_command() -> Result<usize, String> {
+    crate::db::conn::with_conn(compact_history)
+        .await?
+        .map_err(|e| e.to_string())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn get_history(
     conn: &mut SqliteConnection,
     window_id_: &str,
@@ -75,47 +174,20 @@ pub fn get_history(
     input_id_: &str,
     input_title_: &str,
     input_content_: &str,
+    limit: i64,
 ) -> QueryResult<Vec<Input>> {
+    // 只负责收集候选, 排序和取舍由 assembler 按得分和 token 预算决定
     use crate::db::input_history::input::dsl::*;
     let mut result = Vec::new();
     let mut seen = HashSet::new();
+    // 拒绝授权的应用, 授权前保存的历史也不作为上下文
+    let denied = crate::db::app_consent::denied_apps(conn)?;
 
-    // // 5. 当前content (使用 Tantivy 全文搜索)
-    // if let Some(mut search_index_guard) = get_search_index() {
-    //     if let Some(search_index) = search_index_guard.as_mut() {
-    //         if !input_content_.trim().is_empty() {
-    //             match search_index.search(input_content_, 10) {
-    //                 Ok(docs) => {
-    //                     for doc in docs {
-    //                         if let Some(id_val) = doc.get_first(search_index.id_field).and_then(|v| v.as_str()) {
-    //                             use crate::db::input_history::input::dsl::*;
-    //                             let retrieved_input: QueryResult<Input> = input.filter(id.eq(id_val)).first::<Input>(conn);
-    //                             match retrieved_input {
-    //                                 Ok(retrieved_record) => {
-    //                                     if seen.insert(retrieved_record.id.clone()) {
-    //                                         result.push(retrieved_record);
-    //                                     }
-    //                                 },
-    //                                 Err(e) => {
-    //                                     log::error!("Failed to retrieve full Input from DB for id {}: {:?}", id_val, e);
-    //                                 }
-    //                             }
-    //                         }
-    //                     }
-    //                 },
-    //                 Err(e) => {
-    //                     log::error!("Tantivy search failed: {:?}", e);
-    //                 }
-    //             }
-    //         }
-    //     }
-    // }
-
-    // 5. 当前content
+    // 0. 置顶记录 (idx_input_pinned)
     let q = input
-        .filter(input_content.like(format!("%{}%", input_content_)))
+        .filter(pinned.eq(true))
         .order(timestamp.desc())
-        .limit(10)
+        .limit(limit)
         .load::<Input>(conn)?;
     for r in q {
         if seen.insert(r.id.clone()) {
@@ -123,12 +195,33 @@ pub fn get_history(
         }
     }
 
-    // 1. 当前window+input
+    // 5. 当前content (全文索引, 按 BM25 相关度排序; 索引不可用时退回 LIKE)
+    if !input_content_.trim().is_empty() {
+        let q = match search::search_ids(input_content_, limit as usize) {
+            Some(ids) => {
+                let mut rows = input.filter(id.eq_any(&ids)).load::<Input>(conn)?;
+                rows.sort_by_key(|r| ids.iter().position(|i| i == &r.id));
+                rows
+            }
+            None => input
+                .filter(input_content.like(format!("%{}%", escape_like(input_content_))).escape('\\'))
+                .order(timestamp.desc())
+                .limit(limit)
+                .load::<Input>(conn)?,
+        };
+        for r in q {
+            if seen.insert(r.id.clone()) {
+                result.push(r);
+            }
+        }
+    }
+
+    // 1. 当前window+input (idx_input_field)
     let q = input
         .filter(window_id.eq(window_id_))
         .filter(input_id.eq(input_id_))
         .order(timestamp.desc())
-        .limit(10)
+        .limit(limit)
         .load::<Input>(conn)?;
     for r in q {
         if seen.insert(r.id.clone()) {
@@ -136,13 +229,13 @@ pub fn get_history(
         }
     }
 
-    // 1. 同一window+input(重启窗口可能窗口和元素handle会变, 即id会变)
+    // 1. 同一window+input(重启窗口可能窗口和元素handle会变, 即id会变) (idx_input_title_field)
     let q = input
         .filter(window_title.eq(window_title_))
         .filter(window_id.ne(window_id_))
         .filter(input_title.eq(input_title_))
         .order(timestamp.desc())
-        .limit(10)
+        .limit(limit)
         .load::<Input>(conn)?;
     for r in q {
         if seen.insert(r.id.clone()) {
@@ -150,12 +243,12 @@ pub fn get_history(
         }
     }
 
-    // 2. 当前window（不限定input）
+    // 2. 当前window（不限定input） (idx_input_window)
     let q = input
         .filter(window_id.eq(window_id_))
         .filter(input_id.ne(input_id_))
         .order(timestamp.desc())
-        .limit(5)
+        .limit(limit)
         .load::<Input>(conn)?;
     for r in q {
         if seen.insert(r.id.clone()) {
@@ -163,13 +256,13 @@ pub fn get_history(
         }
     }
 
-     // 2. 同一window（不限定input）
+     // 2. 同一window（不限定input） (idx_input_window_title)
      let q = input
         .filter(window_title.eq(window_title_))
         .filter(window_id.ne(window_id_))
         .filter(input_title.ne(input_title_))
         .order(timestamp.desc())
-        .limit(5)
+        .limit(limit)
         .load::<Input>(conn)?;
     for r in q {
         if seen.insert(r.id.clone()) {
@@ -177,12 +270,12 @@ pub fn get_history(
         }
     }
 
-    // 3. 当前app（不限定window）
+    // 3. 当前app（不限定window） (idx_input_app)
     let q = input
         .filter(window_app.eq(window_app_))
         .filter(window_title.ne(window_title_))
         .order(timestamp.desc())
-        .limit(3)
+        .limit(limit)
         .load::<Input>(conn)?;
     for r in q {
         if seen.insert(r.id.clone()) {
@@ -190,11 +283,12 @@ pub fn get_history(
         }
     }
 
-    // 4. 其它应用
+    // 4. 其它应用 (按 idx_input_timestamp 倒序扫描, 取够 limit 条即停)
     let q = input
         .filter(window_app.ne(window_app_))
+        .filter(window_app.ne_all(&denied))
         .order(timestamp.desc())
-        .limit(3)
+        .limit(limit)
         .load::<Input>(conn)?;
     for r in q {
         if seen.insert(r.id.clone()) {
@@ -202,5 +296,91 @@ pub fn get_history(
         }
     }
 
+    result.retain(|r| !denied.contains(&r.window_app));
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{is_draft_of, superseded_drafts};
+    use crate::db::input_history::Input;
+
+    const BACKSPACE_THEN_RETYPE: [&str; 8] =
+        ["hello wrld", "hello wrl", "hello wr", "hello w", "hello wo", "hello wor", "hello worl", "hello world"];
+
+    fn snapshot(id: usize, content: &str) -> Input {
+        Input {
+            id: id.to_string(),
+            window_id: "1".to_string(),
+            window_app: "app.exe".to_string(),
+            window_title: "chat".to_string(),
+            window_class_name: String::new(),
+            window_x: 0,
+            window_y: 0,
+            window_width: 0,
+            window_height: 0,
+            input_id: "input".to_string(),
+            input_title: "message".to_string(),
+            input_control_type: 50004,
+            input_x: 0,
+            input_y: 0,
+            input_width: 0,
+            input_height: 0,
+            input_content: content.to_string(),
+            timestamp: id as i64 * 1000,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn continued_typing_is_draft() {
+        assert!(is_draft_of("Hel", "Hello"));
+        assert!(is_draft_of("", "Hello"));
+        assert!(is_draft_of("你好", "你好世界"));
+    }
+
+    #[test]
+    fn backspacing_is_draft() {
+        assert!(is_draft_of("Hello", "H"));
+        assert!(is_draft_of("hello wrld", "hello w"));
+    }
+
+    // save_history: 每条快照要么覆盖上一条, 要么新增一条
+    #[test]
+    fn backspace_then_retype_keeps_one_entry() {
+        let mut rows: Vec<&str> = Vec::new();
+        for content in BACKSPACE_THEN_RETYPE {
+            match rows.last_mut() {
+                Some(last) if is_draft_of(last, content) => *last = content,
+                _ => rows.push(content),
+            }
+        }
+        assert_eq!(rows, ["hello world"]);
+    }
+
+    #[test]
+    fn compaction_collapses_backspace_then_retype() {
+        let rows: Vec<Input> = BACKSPACE_THEN_RETYPE.iter().enumerate().map(|(i, c)| snapshot(i, c)).collect();
+        let removed = superseded_drafts(rows, 300_000);
+        assert_eq!(removed, (0..7).map(|i| i.to_string()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn compaction_keeps_pinned_and_expired_snapshots() {
+        let mut rows = vec![snapshot(0, "hello"), snapshot(1, "hello world"), snapshot(400, "hello world again")];
+        rows[0].pinned = true;
+        assert!(superseded_drafts(rows, 300_000).is_empty());
+    }
+
+    #[test]
+    fn small_edit_of_similar_length_is_draft() {
+        assert!(is_draft_of("Hello wrold, see you tomorrow", "Hello world, see you tomorrow"));
+        assert!(is_draft_of("see you tomorrow at noon", "see you tomorow at noon"));
+    }
+
+    #[test]
+    fn unrelated_or_much_shorter_text_is_not_draft() {
+        assert!(!is_draft_of("Hello world", "Goodbye"));
+        assert!(!is_draft_of("see you tomorrow at noon", "see you at noon"));
+    }
+}
@@ -1 +1,4 @@
+pub mod browser;
 pub mod history;
+pub mod retention;
+pub mod search;
@@ -0,0 +1,228 @@
+//! 历史保留策略: 按应用/窗口标题的保留天数、条数上限、体积上限和不保存名单
+//! 清理由后台线程定期执行, 不在写入路径上; 置顶的记录不会被清理
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use diesel::sqlite::SqliteConnection;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::config::{self, retention::RetentionRule};
+use crate::db::completion_event::completion_event;
+use crate::db::conn::establish_connection;
+use crate::db::input_history::input;
+use crate::utils::data_dir;
+
+use super::browser::delete_history;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+// 超出体积上限时每轮删除的条数
+const SIZE_BATCH: i64 = 1000;
+const VACUUM_STAMP: &str = "last_vacuum";
+
+// 配置中的正则只编译一次, 无效的正则记为 None 并视为不匹配
+static RE_CACHE: Lazy<Mutex<HashMap<String, Option<Regex>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn title_matches(pattern: &str, title: &str) -> bool {
+    let mut cache = RE_CACHE.lock().unwrap();
+    let re = cache.entry(pattern.to_string()).or_insert_with(|| match Regex::new(pattern) {
+        Ok(re) => Some(re),
+        Err(e) => {
+            warn!("[retention] invalid title pattern {}: {}", pattern, e);
+            None
+        }
+    });
+    re.as_ref().map_or(false, |re| re.is_match(title))
+}
+
+fn rule_matches(rule: &RetentionRule, app: &str, title: &str) -> bool {
+    (rule.app.is_empty() || rule.app.eq_ignore_ascii_case(app))
+        && (rule.title_pattern.is_empty() || title_matches(&rule.title_pattern, title))
+}
+
+/// 该应用/窗口的输入是否不应保存
+pub fn never_store(app: &str, title: &str) -> bool {
+    let config = config::get_config().unwrap().retention;
+    config.never_store_apps.iter().any(|a| a.eq_ignore_ascii_case(app))
+        || config.never_store_titles.iter().any(|p| title_matches(p, title))
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct PurgeSummary {
+    pub expired: usize,
+    pub over_rows: usize,
+    pub over_size: usize,
+    // 超过默认保留天数的补全事件
+    pub events: usize,
+}
+
+impl PurgeSummary {
+    pub fn total(&self) -> usize {
+        self.expired + self.over_rows + self.over_size + self.events
+    }
+}
+
+#[derive(QueryableByName)]
+struct SizeRow {
+    #[diesel(sql_type = BigInt)]
+    bytes: i64,
+}
+
+#[derive(QueryableByName)]
+struct SizedId {
+    #[diesel(sql_type = Text)]
+    id: String,
+    #[diesel(sql_type = BigInt)]
+    bytes: i64,
+}
+
+// 单行历史的字节数: 各文本列的字节长度加上整数列和行头的估计值, 不含索引
+// 只统计 input 表, 审计日志、补全事件等不能或不应由历史清理来腾出空间
+const ROW_BYTES_SQL: &str = "IFNULL(LENGTH(CAST(id AS BLOB)), 0) + IFNULL(LENGTH(CAST(window_id AS BLOB)), 0) \
+    + IFNULL(LENGTH(CAST(window_app AS BLOB)), 0) + IFNULL(LENGTH(CAST(window_title AS BLOB)), 0) \
+    + IFNULL(LENGTH(CAST(window_class_name AS BLOB)), 0) + IFNULL(LENGTH(CAST(input_id AS BLOB)), 0) \
+    + IFNULL(LENGTH(CAST(input_title AS BLOB)), 0) + IFNULL(LENGTH(CAST(input_content AS BLOB)), 0) + 64";
+
+fn history_bytes(conn: &mut SqliteConnection) -> QueryResult<i64> {
+    let rows: Vec<SizeRow> = diesel::sql_query(format!("SELECT COALESCE(SUM({}), 0) AS bytes FROM input", ROW_BYTES_SQL)).load(conn)?;
+    Ok(rows.first().map_or(0, |r| r.bytes))
+}
+
+fn oldest_unpinned_sized(conn: &mut SqliteConnection, limit: i64) -> QueryResult<Vec<SizedId>> {
+    diesel::sql_query(format!(
+        "SELECT id, {} AS bytes FROM input WHERE pinned = 0 ORDER BY timestamp ASC LIMIT ?",
+        ROW_BYTES_SQL
+    ))
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+}
+
+fn oldest_unpinned(conn: &mut SqliteConnection, limit: i64) -> QueryResult<Vec<String>> {
+    use crate::db::input_history::input::dsl::*;
+    input.filter(pinned.eq(false)).order(timestamp.asc()).limit(limit).select(id).load::<String>(conn)
+}
+
+fn expired_ids(conn: &mut SqliteConnection, default_ttl: u64, rules: &[RetentionRule]) -> QueryResult<Vec<String>> {
+    use crate::db::input_history::input::dsl::*;
+    // 比最短保留天数还新的记录不可能过期, 不必读出
+    let Some(min_ttl) = rules.iter().map(|r| r.ttl_days).chain([default_ttl]).filter(|t| *t > 0).min() else {
+        return Ok(Vec::new());
+    };
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
+    let rows = input
+        .filter(pinned.eq(false))
+        .filter(timestamp.lt(now - min_ttl as i64 * DAY_MILLIS))
+        .select((id, window_app, window_title, timestamp))
+        .load::<(String, String, String, i64)>(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, app, title, ts)| {
+            let ttl = rules.iter().find(|r| rule_matches(r, app, title)).map_or(default_ttl, |r| r.ttl_days);
+            ttl > 0 && *ts < now - ttl as i64 * DAY_MILLIS
+        })
+        .map(|(record_id, _, _, _)| record_id)
+        .collect())
+}
+
+/// 依次按保留天数、条数上限、体积上限删除历史, 补全事件按默认保留天数清理
+pub fn purge(conn: &mut SqliteConnection) -> QueryResult<PurgeSummary> {
+    let config = config::get_config().unwrap();
+    let retention = config.retention;
+    let mut summary = PurgeSummary::default();
+
+    let ids = expired_ids(conn, config.system.history_ttl, &retention.rules)?;
+    summary.expired = delete_history(conn, &ids)?;
+
+    if config.system.history_ttl > 0 {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
+        let cutoff = now - config.system.history_ttl as i64 * DAY_MILLIS;
+        summary.events = diesel::delete(completion_event::table.filter(completion_event::timestamp.lt(cutoff))).execute(conn)?;
+    }
+
+    if retention.max_rows > 0 {
+        let count = input::table.filter(input::pinned.eq(false)).count().get_result::<i64>(conn)?;
+        let excess = count - retention.max_rows as i64;
+        if excess > 0 {
+            let ids = oldest_unpinned(conn, excess)?;
+            summary.over_rows = delete_history(conn, &ids)?;
+        }
+    }
+
+    if retention.max_size_mb > 0 {
+        let cap = retention.max_size_mb as i64 * 1024 * 1024;
+        let mut bytes = history_bytes(conn)?;
+        while bytes > cap {
+            let rows = oldest_unpinned_sized(conn, SIZE_BATCH)?;
+            if rows.is_empty() {
+                break;
+            }
+            // 每轮只删到低于上限为止
+            let mut ids = Vec::new();
+            for row in rows {
+                if bytes <= cap {
+                    break;
+                }
+                bytes -= row.bytes;
+                ids.push(row.id);
+            }
+            summary.over_size += delete_history(conn, &ids)?;
+        }
+    }
+    Ok(summary)
+}
+
+// 上次 VACUUM 的时间保存在数据目录中, 应用经常重启时也能按间隔执行
+fn vacuum_due(interval_hours: u64) -> bool {
+    if interval_hours == 0 {
+        return false;
+    }
+    match std::fs::metadata(data_dir::path(VACUUM_STAMP)).and_then(|m| m.modified()) {
+        Ok(last) => last.elapsed().map_or(false, |elapsed| elapsed >= Duration::from_secs(interval_hours * 3600)),
+        // 首次运行时从现在开始计时
+        Err(_) => {
+            let _ = std::fs::write(data_dir::path(VACUUM_STAMP), b"");
+            false
+        }
+    }
+}
+
+fn vacuum(conn: &mut SqliteConnection) {
+    match diesel::sql_query("VACUUM").execute(conn) {
+        Ok(_) => {
+            info!("[retention::vacuum] database vacuumed");
+            if let Err(e) = std::fs::write(data_dir::path(VACUUM_STAMP), b"") {
+                error!("[retention::vacuum] failed to write stamp: {}", e);
+            }
+        }
+        Err(e) => error!("[retention::vacuum] failed: {}", e),
+    }
+}
+
+pub fn start_scheduler() {
+    std::thread::spawn(|| loop {
+        let config = config::get_config().unwrap().retention;
+        {
+            let mut conn = establish_connection();
+            match purge(&mut conn) {
+                Ok(summary) if summary.total() > 0 => info!("[retention] purged history: {:?}", summary),
+                Ok(_) => {}
+                Err(e) => error!("[retention] purge failed: {}", e),
+            }
+            if vacuum_due(config.vacuum_interval) {
+                vacuum(&mut conn);
+            }
+        }
+        std::thread::sleep(Duration::from_secs(config.purge_interval.max(60)));
+    });
+}
+
+#[tauri::command]
+pub async fn purge_history_command() -> Result<PurgeSummary, String> {
+    crate::db::conn::with_conn(purge).await?.map_err(|e| e.to_string())
+}
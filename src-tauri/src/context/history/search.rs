@@ -1,47 +1,181 @@
 use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
-use tantivy::schema::{Field, Schema, TextFieldIndexing, TextOptions};
-use tantivy::{doc, Index, IndexWriter, ReloadPolicy, TantivyDocument};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, STORED, STRING};
+use tantivy::tokenizer::{TextAnalyzer, Token, TokenStream, Tokenizer};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use log::{info, error};
-use tantivy::tokenizer::{TextAnalyzer, SimpleTokenizer, LowerCaser, Stemmer, Language};
+
+use crate::config::storage::Encryption;
+use crate::db::input_history::Input;
+
+pub const TOKENIZER_NAME: &str = "cjk";
+// 英文等按词切分时, 额外索引的前缀最大长度, 用于匹配正在输入的半个词
+const MAX_PREFIX_LEN: usize = 12;
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'   // 平假名、片假名
+        | '\u{3400}'..='\u{4DBF}' // CJK 扩展 A
+        | '\u{4E00}'..='\u{9FFF}' // CJK 统一表意文字
+        | '\u{AC00}'..='\u{D7AF}' // 韩文音节
+        | '\u{F900}'..='\u{FAFF}' // CJK 兼容表意文字
+    )
+}
+
+fn push_token(tokens: &mut Vec<Token>, text: String, offset_from: usize, offset_to: usize) {
+    let position = tokens.len();
+    tokens.push(Token {
+        offset_from,
+        offset_to,
+        position,
+        text,
+        position_length: 1,
+    });
+}
+
+// CJK 文本切为单字和相邻二字, 其它文字按词切分并转小写; with_prefixes 时额外输出词的前缀
+pub fn tokenize(text: &str, with_prefixes: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let end_of = |i: usize| if i + 1 < chars.len() { chars[i + 1].0 } else { text.len() };
+    let mut i = 0;
+    while i < chars.len() {
+        let (offset, c) = chars[i];
+        if is_cjk(c) {
+            push_token(&mut tokens, c.to_string(), offset, end_of(i));
+            if i + 1 < chars.len() && is_cjk(chars[i + 1].1) {
+                let bigram: String = [c, chars[i + 1].1].iter().collect();
+                push_token(&mut tokens, bigram, offset, end_of(i + 1));
+            }
+            i += 1;
+        } else if c.is_alphanumeric() {
+            let start = i;
+            while i < chars.len() && chars[i].1.is_alphanumeric() && !is_cjk(chars[i].1) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().map(|(_, c)| c).collect::<String>().to_lowercase();
+            let word_chars: Vec<char> = word.chars().collect();
+            if with_prefixes {
+                for len in 2..word_chars.len().min(MAX_PREFIX_LEN + 1) {
+                    let prefix: String = word_chars[..len].iter().collect();
+                    push_token(&mut tokens, prefix, offset, end_of(start + len - 1));
+                }
+            }
+            push_token(&mut tokens, word, offset, end_of(i - 1));
+        } else {
+            i += 1;
+        }
+    }
+    tokens
+}
+
+// 查询文本按同样规则切词后任一词命中即可, 由 BM25 排序
+pub fn token_query(field: Field, query_str: &str) -> Option<BooleanQuery> {
+    let mut seen = HashSet::new();
+    let clauses: Vec<(Occur, Box<dyn Query>)> = tokenize(query_str, false)
+        .into_iter()
+        .filter(|t| seen.insert(t.text.clone()))
+        .map(|t| {
+            let term = Term::from_field_text(field, &t.text);
+            (Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)) as Box<dyn Query>)
+        })
+        .collect();
+    if clauses.is_empty() {
+        return None;
+    }
+    Some(BooleanQuery::new(clauses))
+}
+
+#[derive(Clone)]
+pub struct CjkTokenizer;
+
+pub struct CjkTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl TokenStream for CjkTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.index < self.tokens.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
+
+impl Tokenizer for CjkTokenizer {
+    type TokenStream<'a> = CjkTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CjkTokenStream {
+        CjkTokenStream { tokens: tokenize(text, true), index: 0 }
+    }
+}
 
 // Define the schema for our search index
 pub struct SearchIndex {
-    index: Index,
+    reader: IndexReader,
     pub input_content_field: Field,
     pub id_field: Field,
     index_writer: Arc<Mutex<IndexWriter>>,
 }
 
-impl SearchIndex {
-    pub fn new(index_path: PathBuf) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let mut schema_builder = Schema::builder();
+fn build_schema() -> (Schema, Field, Field) {
+    let mut schema_builder = Schema::builder();
 
-        // Text options for fields that will be searched
-        let text_options = TextOptions::default()
-            .set_indexing_options(TextFieldIndexing::default()
-                .set_tokenizer("en_stemmed") // Use a tokenizer for better search
-                .set_index_option(tantivy::schema::IndexRecordOption::WithFreqsAndPositions))
-            .set_stored(); // Store the content so we can retrieve it
+    // 只索引不存储, 原文只保存在数据库中
+    let text_options = TextOptions::default()
+        .set_indexing_options(TextFieldIndexing::default()
+            .set_tokenizer(TOKENIZER_NAME)
+            .set_index_option(IndexRecordOption::WithFreqs));
 
-        let input_content_field = schema_builder.add_text_field("input_content", text_options.clone());
-        let id_field = schema_builder.add_text_field("id", TextOptions::default().set_stored());
+    let input_content_field = schema_builder.add_text_field("input_content", text_options);
+    let id_field = schema_builder.add_text_field("id", STRING | STORED);
+    (schema_builder.build(), input_content_field, id_field)
+}
+
+impl SearchIndex {
+    // index_path 为 None 时索引只保存在内存中
+    pub fn new(index_path: Option<PathBuf>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let (schema, input_content_field, id_field) = build_schema();
 
-        let schema = schema_builder.build();
+        let index = match &index_path {
+            Some(index_path) => {
+                std::fs::create_dir_all(index_path)?;
+                match Index::open_or_create(MmapDirectory::open(index_path)?, schema.clone()) {
+                    Ok(index) => index,
+                    Err(e) => {
+                        // 旧版本或损坏的索引, 删除后重建
+                        error!("Failed to open search index, recreating: {:?}", e);
+                        std::fs::remove_dir_all(index_path)?;
+                        std::fs::create_dir_all(index_path)?;
+                        Index::create_in_dir(index_path, schema)?
+                    }
+                }
+            }
+            None => Index::create_in_ram(schema),
+        };
 
-        // Create or open the index
-        let index = Index::create_in_dir(&index_path, schema.clone())?;
-        
-        // Add a tokenizer for English stemming (or other languages if needed)
-        index.tokenizers().register(
-            "en_stemmed",
-            TextAnalyzer::from(SimpleTokenizer::default())
-                .filter(LowerCaser)
-                .filter(Stemmer::new(Language::English))
-        );
+        index.tokenizers().register(TOKENIZER_NAME, TextAnalyzer::from(CjkTokenizer));
 
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
 
         let index_writer = index.writer(50_000_000)?; // 50 MB heap size for indexing
         let index_writer = Arc::new(Mutex::new(index_writer));
@@ -49,36 +183,78 @@ impl SearchIndex {
         info!("Tantivy search index initialized at {:?}", index_path);
 
         Ok(SearchIndex {
-            index,
+            reader,
             input_content_field,
             id_field,
             index_writer,
         })
     }
 
-    pub fn add_document(&self, doc: TantivyDocument) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut writer = self.index_writer.lock().unwrap();
-        writer.add_document(doc)?;
-        writer.commit()?; // Commit changes immediately for simplicity, consider batching for performance
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    fn commit(&self, writer: &mut IndexWriter) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        writer.commit()?;
+        self.reader.reload()?;
         Ok(())
     }
 
-    pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<TantivyDocument>, Box<dyn std::error::Error + Send + Sync>> {
-        let reader = self.index.reader()?;
-        let searcher = reader.searcher();
+    // 新增或替换一批历史, 只提交一次; 同一 id 出现多次时保留最后一条
+    pub fn add_records(&self, records: &[Input]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let mut writer = self.index_writer.lock().unwrap();
+        for record in records {
+            writer.delete_term(Term::from_field_text(self.id_field, &record.id));
+            writer.add_document(doc!(
+                self.input_content_field => record.input_content.clone(),
+                self.id_field => record.id.clone(),
+            ))?;
+        }
+        self.commit(&mut writer)
+    }
 
-        let query_parser = QueryParser::for_index(&self.index, vec![
-            self.input_content_field,
-        ]);
+    pub fn delete_records(&self, ids: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut writer = self.index_writer.lock().unwrap();
+        for id in ids {
+            writer.delete_term(Term::from_field_text(self.id_field, id));
+        }
+        self.commit(&mut writer)
+    }
+
+    // 清空后按数据库重建
+    pub fn rebuild(&self, records: &[Input]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut writer = self.index_writer.lock().unwrap();
+        writer.delete_all_documents()?;
+        for record in records {
+            writer.add_document(doc!(
+                self.input_content_field => record.input_content.clone(),
+                self.id_field => record.id.clone(),
+            ))?;
+        }
+        self.commit(&mut writer)
+    }
 
-        let query = query_parser.parse_query(query_str)?;
+    // BM25 排序, 返回 (id, score)
+    pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<(String, f32)>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(query) = token_query(self.input_content_field, query_str) else {
+            return Ok(Vec::new());
+        };
 
+        let searcher = self.reader.searcher();
         let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
 
         let mut results = Vec::new();
-        for (_score, doc_address) in top_docs {
-            let retrieved_doc = searcher.doc(doc_address)?;
-            results.push(retrieved_doc);
+        for (score, doc_address) in top_docs {
+            let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+            if let Some(id) = retrieved_doc.get_first(self.id_field).and_then(|v| v.as_str()) {
+                results.push((id.to_string(), score));
+            }
         }
         Ok(results)
     }
@@ -88,8 +264,13 @@ impl SearchIndex {
 use once_cell::sync::Lazy;
 pub static SEARCH_INDEX: Lazy<Mutex<Option<SearchIndex>>> = Lazy::new(|| Mutex::new(None));
 
-pub fn init_search_index(data_dir: PathBuf) {
-    let index_path = data_dir.join("tantivy_index");
+const INDEX_DIR: &str = "search_index";
+// 数据目录中存在此文件时, 下次启动重建索引(命令行导入等不打开索引的写入)
+const STALE_MARKER: &str = "search_index.stale";
+// 索引与数据库一致时才用于检索; 重建期间为 false, 检索退回 LIKE, 且不等待重建持有的锁
+static INDEX_READY: AtomicBool = AtomicBool::new(false);
+
+pub fn init_search_index(index_path: Option<PathBuf>) {
     match SearchIndex::new(index_path) {
         Ok(index) => {
             *SEARCH_INDEX.lock().unwrap() = Some(index);
@@ -104,3 +285,122 @@ pub fn init_search_index(data_dir: PathBuf) {
 pub fn get_search_index() -> Option<std::sync::MutexGuard<'static, Option<SearchIndex>>> {
     SEARCH_INDEX.lock().ok()
 }
+
+// 数据库加密时索引只放在内存中, 避免词项明文落盘
+// 在后台线程核对索引文档数与历史条数, 不一致(上次写入中断、索引为空等)时从数据库重建, 不阻塞启动
+pub fn init() {
+    let encrypted = crate::config::get_config().unwrap().storage.encryption != Encryption::None;
+    let index_path = if encrypted { None } else { Some(crate::utils::data_dir::path(INDEX_DIR)) };
+    init_search_index(index_path);
+    std::thread::spawn(|| {
+        let marker = crate::utils::data_dir::path(STALE_MARKER);
+        if marker.exists() {
+            info!("[init] search index marked stale, rebuilding");
+            match rebuild_from_db() {
+                Ok(n) => {
+                    info!("[init] search index rebuilt, {} records", n);
+                    if let Err(e) = std::fs::remove_file(&marker) {
+                        error!("[init] failed to remove {:?}: {}", marker, e);
+                    }
+                }
+                Err(e) => error!("[init] failed to rebuild search index: {}", e),
+            }
+            return;
+        }
+        let num_docs = match get_search_index() {
+            Some(guard) => match guard.as_ref() {
+                Some(index) => index.num_docs(),
+                None => return,
+            },
+            None => return,
+        };
+        match count_history() {
+            Ok(count) if count == num_docs => {
+                INDEX_READY.store(true, Ordering::SeqCst);
+                return;
+            }
+            Ok(count) => info!("[init] search index has {} docs but history has {}, rebuilding", num_docs, count),
+            Err(e) => error!("[init] failed to count history, rebuilding search index: {}", e),
+        }
+        match rebuild_from_db() {
+            Ok(n) => info!("[init] search index rebuilt, {} records", n),
+            Err(e) => error!("[init] failed to rebuild search index: {}", e),
+        }
+    });
+}
+
+pub fn mark_stale() {
+    let marker = crate::utils::data_dir::path(STALE_MARKER);
+    if let Err(e) = std::fs::write(&marker, b"") {
+        error!("[mark_stale] failed to write {:?}: {}", marker, e);
+    }
+}
+
+fn count_history() -> Result<u64, String> {
+    use crate::db::input_history::input::dsl::*;
+    use diesel::prelude::*;
+    let mut conn = crate::db::conn::establish_connection();
+    input.count().get_result::<i64>(&mut conn).map(|n| n as u64).map_err(|e| e.to_string())
+}
+
+pub fn index_record(record: &Input) {
+    index_records(std::slice::from_ref(record));
+}
+
+pub fn index_records(records: &[Input]) {
+    if let Some(guard) = get_search_index() {
+        if let Some(index) = guard.as_ref() {
+            if let Err(e) = index.add_records(records) {
+                error!("[index_records] failed to index {} histories: {:?}", records.len(), e);
+            }
+        }
+    }
+}
+
+pub fn remove_records(ids: &[String]) {
+    if let Some(guard) = get_search_index() {
+        if let Some(index) = guard.as_ref() {
+            if let Err(e) = index.delete_records(ids) {
+                error!("[remove_records] failed to delete from search index: {:?}", e);
+            }
+        }
+    }
+}
+
+// 返回按相关度排序的历史 id, 索引不可用或正在重建时返回 None
+pub fn search_ids(query: &str, limit: usize) -> Option<Vec<String>> {
+    if !INDEX_READY.load(Ordering::SeqCst) {
+        return None;
+    }
+    let guard = get_search_index()?;
+    let index = guard.as_ref()?;
+    match index.search(query, limit) {
+        Ok(results) => Some(results.into_iter().map(|(id, _)| id).collect()),
+        Err(e) => {
+            error!("[search_ids] search failed: {:?}", e);
+            None
+        }
+    }
+}
+
+// 重建失败时索引保持不可用, 检索继续使用 LIKE
+// 先持有索引锁再读数据库, 重建期间写入的历史在重建完成后再加入索引, 不会被清掉
+pub fn rebuild_from_db() -> Result<usize, String> {
+    use crate::db::input_history::input::dsl::*;
+    use diesel::prelude::*;
+    INDEX_READY.store(false, Ordering::SeqCst);
+    let guard = get_search_index().ok_or("search index lock poisoned")?;
+    let index = guard.as_ref().ok_or("search index not initialized")?;
+    let mut conn = crate::db::conn::establish_connection();
+    let records = input.load::<Input>(&mut conn).map_err(|e| e.to_string())?;
+    index.rebuild(&records).map_err(|e| e.to_string())?;
+    INDEX_READY.store(true, Ordering::SeqCst);
+    Ok(records.len())
+}
+
+#[tauri::command]
+pub async fn rebuild_search_index_command() -> Result<usize, String> {
+    tokio::task::spawn_blocking(rebuild_from_db)
+        .await
+        .map_err(|e| e.to_string())?
+}
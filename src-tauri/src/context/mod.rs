@@ -1,21 +1,34 @@
+pub mod assembler;
 pub mod history;
+pub mod outbound;
+pub mod source;
 
 use log::debug;
 use serde::Serialize;
 
-use crate::os::clipboard::windows_clipboard::get_clipboard_history;
 use crate::os::element::ui_automation::FocusedInput;
-use crate::db::conn::establish_connection;
-use crate::context::history::history::get_history;
+use crate::context::assembler::{assemble, ScoredItem};
+use crate::context::source::{fetch_all, SourceItem};
+use crate::utils::logger::redact_debug;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Context {
     pub app: InputContext,
-    pub history: Vec<InputContext>,
-    pub clipboard_history: Vec<String>,
+    // 各上下文来源选中的条目和渲染结果, 按来源优先级排序
+    pub sources: Vec<SourceContext>,
+    // 每条候选的得分和取舍结果, 用于调试
+    pub selection: Vec<ScoredItem>,
 }
 
 #[derive(Debug, Clone, Serialize)]
+pub struct SourceContext {
+    pub name: &'static str,
+    pub items: Vec<SourceItem>,
+    // 模板变量 {{name}} 的值
+    pub rendered: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct InputContext {
     pub window_id: String,
     pub window_app: String,
@@ -25,54 +38,28 @@ pub struct InputContext {
     pub input_content: String,
 }
 
-fn truncate_input_content(s: &str) -> String {
-    let chars: Vec<_> = s.chars().collect();
-    let len = chars.len();
-    chars[len.saturating_sub(1024)..].iter().collect()
-}
-
 impl Context {
-    pub fn new(app: &FocusedInput) -> Option<Self> {
+    pub async fn new(app: &FocusedInput) -> Option<Self> {
+        debug!("[Context::new] app: {}", redact_debug(app));
+        let template = crate::config::get_config().unwrap().ai_client.prompt;
+        let fetched = fetch_all(app, &template).await;
+        let assembled = assemble(app, fetched);
+        debug!("[Context::new] selection: {:?}", assembled.items);
         let app_context = InputContext {
             window_id: app.window_element.id.to_string(),
             window_app: app.window_element.app.clone(),
             window_title: app.window_element.title.clone(),
             input_id: app.input_element.id.clone(),
             input_title: app.input_element.text.clone(),
-            input_content: truncate_input_content(&app.input_element.content),
-        };
-        let mut conn = establish_connection();
-        debug!("[Context::new] app: {:?}", app);
-        let history = match get_history(
-            &mut conn,
-            &app.window_element.id.to_string(),
-            &app.window_element.class_name,
-            &app.window_element.title,
-            &app.input_element.id,
-            &app.input_element.text,
-            &app.input_element.content,
-        ) {
-            Ok(history) => history,
-            Err(e) => {
-                log::error!("get_history error: {:?}", e);
-                vec![]
-            }
+            input_content: assembled.input_content,
         };
-        let context_input_history = history.iter().map(|h| InputContext {
-            window_id: h.window_id.clone(),
-            window_app: h.window_app.clone(),
-            window_title: h.window_title.clone(),
-            input_id: h.input_id.clone(),
-            input_title: h.input_title.clone(),
-            input_content: truncate_input_content(&h.input_content),
-        }).collect::<Vec<InputContext>>();
-        debug!("[Context::new] history: {:?}", context_input_history);
-        let clipboard_history = get_clipboard_history();
-        debug!("[Context::new] clipboard_history: {:?}", clipboard_history);
+        for source in &assembled.sources {
+            debug!("[Context::new] {}: {}", source.name, redact_debug(&source.items));
+        }
         Some(Self {
             app: app_context,
-            history: context_input_history,
-            clipboard_history,
+            sources: assembled.sources,
+            selection: assembled.items,
         })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,123 @@
+//! 按外发策略把 Context 转成 prompt 变量, 决定每个字段原样发送、发送哈希或不发送
+
+use serde_json::{json, Map, Value};
+
+use crate::config::{self, context::HistoryFormat, privacy::{FieldPolicy, OutboundPolicyConfig}};
+use crate::utils::hash::salted_hash;
+
+use super::{Context, InputContext};
+
+pub struct OutboundFields {
+    pub app_name: String,
+    pub window_title: String,
+    pub window_handle: String,
+    pub input_title: String,
+    pub input_handle: String,
+    pub input_content: String,
+    // 上下文来源渲染后的模板变量
+    pub sources: Vec<(&'static str, String)>,
+}
+
+fn apply_policy(value: &str, policy: FieldPolicy) -> Option<String> {
+    match policy {
+        FieldPolicy::Include => Some(value.to_string()),
+        FieldPolicy::Hash => Some(format!("#{}", &salted_hash(value)[..8])),
+        FieldPolicy::Drop => None,
+    }
+}
+
+fn history_entry_json(entry: &InputContext, policy: &OutboundPolicyConfig) -> Option<Value> {
+    let content = apply_policy(&entry.input_content, policy.history)?;
+    let mut map = Map::new();
+    let fields = [
+        ("window_id", &entry.window_id, policy.handles),
+        ("window_app", &entry.window_app, policy.app_name),
+        ("window_title", &entry.window_title, policy.window_title),
+        ("input_id", &entry.input_id, policy.handles),
+        ("input_title", &entry.input_title, policy.input_title),
+    ];
+    for (name, value, field_policy) in fields {
+        if let Some(value) = apply_policy(value, field_policy) {
+            map.insert(name.to_string(), Value::String(value));
+        }
+    }
+    map.insert("input_content".to_string(), Value::String(content));
+    Some(Value::Object(map))
+}
+
+fn history_entry_compact(entry: &InputContext, policy: &OutboundPolicyConfig) -> Option<String> {
+    let content = apply_policy(&entry.input_content, policy.history)?;
+    let mut labels = Vec::new();
+    labels.extend(apply_policy(&entry.window_app, policy.app_name));
+    labels.extend(apply_policy(&entry.window_title, policy.window_title));
+    labels.extend(apply_policy(&entry.input_title, policy.input_title));
+    if let (Some(window_id), Some(input_id)) = (apply_policy(&entry.window_id, policy.handles), apply_policy(&entry.input_id, policy.handles)) {
+        labels.push(format!("{}/{}", window_id, input_id));
+    }
+    let content = content.replace('\r', "").replace('\n', "\\n");
+    if labels.is_empty() {
+        Some(content)
+    } else {
+        Some(format!("[{}] {}", labels.join(" | "), content))
+    }
+}
+
+pub fn format_history(history: &[InputContext]) -> String {
+    let config = config::get_config().unwrap();
+    let policy = &config.privacy.outbound;
+    match config.context.history_format {
+        HistoryFormat::Json => {
+            let entries: Vec<Value> = history.iter().filter_map(|h| history_entry_json(h, policy)).collect();
+            if entries.is_empty() {
+                return String::new();
+            }
+            Value::Array(entries).to_string()
+        }
+        HistoryFormat::Compact => history
+            .iter()
+            .filter_map(|h| history_entry_compact(h, policy))
+            .collect::<Vec<String>>()
+            .join("\n"),
+    }
+}
+
+pub fn format_clipboard(clipboard_history: &[String]) -> String {
+    let policy = config::get_config().unwrap().privacy.outbound.clipboard;
+    let entries: Vec<String> = clipboard_history.iter().filter_map(|c| apply_policy(c, policy)).collect();
+    if entries.is_empty() {
+        return String::new();
+    }
+    json!(entries).to_string()
+}
+
+// 每条一行, 标签和提示加前缀; kind 为 None 的是普通界面文本
+pub fn format_screen_context(texts: &[(Option<&str>, &str)]) -> String {
+    let policy = config::get_config().unwrap().privacy.outbound.screen_context;
+    texts
+        .iter()
+        .filter_map(|(kind, text)| {
+            let text = apply_policy(text, policy)?.replace('\r', "").replace('\n', " ");
+            Some(match kind {
+                Some(kind) => format!("[{}] {}", kind, text),
+                None => text,
+            })
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+impl OutboundFields {
+    pub fn from_context(context: &Context) -> Self {
+        let policy = config::get_config().unwrap().privacy.outbound;
+        let app = &context.app;
+        Self {
+            app_name: apply_policy(&app.window_app, policy.app_name).unwrap_or_default(),
+            window_title: apply_policy(&app.window_title, policy.window_title).unwrap_or_default(),
+            window_handle: apply_policy(&app.window_id, policy.handles).unwrap_or_default(),
+            input_title: apply_policy(&app.input_title, policy.input_title).unwrap_or_default(),
+            input_handle: apply_policy(&app.input_id, policy.handles).unwrap_or_default(),
+            input_content: app.input_content.clone(),
+            sources: context.sources.iter().map(|s| (s.name, s.rendered.clone())).collect(),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use futures_util::future::BoxFuture;
+
+use crate::config::context::SectionBudget;
+use crate::context::outbound::format_clipboard;
+use crate::context::InputContext;
+use crate::os::clipboard::windows_clipboard::get_clipboard_history;
+use crate::os::element::ui_automation::FocusedInput;
+
+use super::{ContextSource, SourceItem};
+
+pub struct ClipboardSource;
+
+impl ContextSource for ClipboardSource {
+    fn name(&self) -> &'static str {
+        "clipboard_contents"
+    }
+
+    fn priority(&self) -> i32 {
+        5
+    }
+
+    fn budget_hint(&self) -> Option<SectionBudget> {
+        Some(SectionBudget { max_tokens: 300, min_items: 1 })
+    }
+
+    fn fetch<'a>(&'a self, _input: &'a FocusedInput) -> BoxFuture<'a, Result<Vec<SourceItem>, String>> {
+        Box::pin(async move {
+            Ok(get_clipboard_history()
+                .into_iter()
+                .map(|c| SourceItem {
+                    id: c.timestamp.to_string(),
+                    text: c.text,
+                    timestamp: Some(c.timestamp),
+                    origin: Some(InputContext { window_app: c.app, ..Default::default() }),
+                    pinned: false,
+                    nearby_kind: None,
+                })
+                .collect())
+        })
+    }
+
+    fn render(&self, items: &[SourceItem]) -> String {
+        let texts: Vec<String> = items.iter().map(|i| i.text.clone()).collect();
+        format_clipboard(&texts)
+    }
+}
@@ -0,0 +1,31 @@
+use futures_util::future::BoxFuture;
+use time::OffsetDateTime;
+
+use crate::os::element::ui_automation::FocusedInput;
+
+use super::{ContextSource, SourceItem};
+
+// 当前本地日期时间, 如 "2025-06-01 14:30 Sunday"
+pub struct DateTimeSource;
+
+impl ContextSource for DateTimeSource {
+    fn name(&self) -> &'static str {
+        "datetime"
+    }
+
+    fn fetch<'a>(&'a self, _input: &'a FocusedInput) -> BoxFuture<'a, Result<Vec<SourceItem>, String>> {
+        Box::pin(async move {
+            let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+            let text = format!(
+                "{:04}-{:02}-{:02} {:02}:{:02} {}",
+                now.year(),
+                now.month() as u8,
+                now.day(),
+                now.hour(),
+                now.minute(),
+                now.weekday()
+            );
+            Ok(vec![SourceItem { id: "now".to_string(), text, timestamp: None, origin: None, pinned: false, nearby_kind: None }])
+        })
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+
+use crate::config::{self, context::SectionBudget};
+use crate::context::history::history::get_history;
+use crate::context::outbound::format_history;
+use crate::context::InputContext;
+use crate::db::conn::with_conn;
+use crate::os::element::ui_automation::FocusedInput;
+
+use super::{ContextSource, SourceItem};
+
+pub struct HistorySource;
+
+impl ContextSource for HistorySource {
+    fn name(&self) -> &'static str {
+        "input_history"
+    }
+
+    fn priority(&self) -> i32 {
+        10
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(1000)
+    }
+
+    fn budget_hint(&self) -> Option<SectionBudget> {
+        Some(SectionBudget { max_tokens: 1200, min_items: 3 })
+    }
+
+    fn fetch<'a>(&'a self, input: &'a FocusedInput) -> BoxFuture<'a, Result<Vec<SourceItem>, String>> {
+        let app = input.clone();
+        Box::pin(async move {
+            with_conn(move |conn| -> Result<Vec<SourceItem>, String> {
+                let candidate_limit = config::get_config().unwrap().context.candidate_limit;
+                let history = get_history(
+                    conn,
+                    &app.window_element.id.to_string(),
+                    &app.window_element.app,
+                    &app.window_element.title,
+                    &app.input_element.id,
+                    &app.input_element.text,
+                    &app.input_element.content,
+                    candidate_limit,
+                )
+                .map_err(|e| e.to_string())?;
+                Ok(history
+                    .into_iter()
+                    .map(|h| SourceItem {
+                        id: h.id,
+                        text: h.input_content,
+                        timestamp: Some(h.timestamp),
+                        origin: Some(InputContext {
+                            window_id: h.window_id,
+                            window_app: h.window_app,
+                            window_title: h.window_title,
+                            input_id: h.input_id,
+                            input_title: h.input_title,
+                            input_content: String::new(),
+                        }),
+                        pinned: h.pinned,
+                        nearby_kind: None,
+                    })
+                    .collect())
+            })
+            .await?
+        })
+    }
+
+    fn render(&self, items: &[SourceItem]) -> String {
+        let history: Vec<InputContext> = items
+            .iter()
+            .map(|item| InputContext {
+                input_content: item.text.clone(),
+                ..item.origin.clone().unwrap_or_default()
+            })
+            .collect();
+        format_history(&history)
+    }
+}
@@ -0,0 +1,200 @@
+//! 个人知识库: 定期扫描目录, 切片后建立内存索引, 按当前输入和窗口标题检索
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use futures_util::future::BoxFuture;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use tantivy::collector::TopDocs;
+use tantivy::schema::{Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, STORED, STRING};
+use tantivy::tokenizer::TextAnalyzer;
+use tantivy::{doc, Index, IndexReader, TantivyDocument};
+
+use crate::config::{self, context::SectionBudget};
+use crate::context::history::search::{token_query, CjkTokenizer, TOKENIZER_NAME};
+use crate::os::element::ui_automation::FocusedInput;
+
+use super::{ContextSource, SourceItem};
+
+// 检索时只取输入框末尾这么多字符
+const QUERY_TAIL_CHARS: usize = 200;
+
+struct KnowledgeIndex {
+    reader: IndexReader,
+    id_field: Field,
+    text_field: Field,
+}
+
+static KNOWLEDGE_INDEX: Lazy<RwLock<Option<KnowledgeIndex>>> = Lazy::new(|| RwLock::new(None));
+
+// (路径, 修改时间, 大小), 变化时重建索引
+type Snapshot = Vec<(PathBuf, SystemTime, u64)>;
+
+fn scan(dir: &Path, extensions: &[String], files: &mut Snapshot) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("[knowledge::scan] failed to read {:?}: {}", dir, e);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else { continue };
+        if metadata.is_dir() {
+            scan(&path, extensions, files);
+            continue;
+        }
+        let matches = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map_or(false, |e| extensions.iter().any(|x| x.eq_ignore_ascii_case(e)));
+        if matches {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((path, modified, metadata.len()));
+        }
+    }
+}
+
+// 按空行分段, 相邻段落合并到不超过 chunk_chars, 超长段落直接按字符切开
+fn chunk(text: &str, chunk_chars: usize) -> Vec<String> {
+    let chunk_chars = chunk_chars.max(1);
+    let text = text.replace("\r\n", "\n");
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let chars: Vec<char> = paragraph.chars().collect();
+        for piece in chars.chunks(chunk_chars) {
+            let piece: String = piece.iter().collect();
+            if !current.is_empty() && current.chars().count() + piece.chars().count() + 2 > chunk_chars {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn build_index(folder: &Path, files: &Snapshot, chunk_chars: usize) -> Result<(KnowledgeIndex, usize), Box<dyn std::error::Error + Send + Sync>> {
+    let mut schema_builder = Schema::builder();
+    let text_options = TextOptions::default()
+        .set_indexing_options(TextFieldIndexing::default()
+            .set_tokenizer(TOKENIZER_NAME)
+            .set_index_option(IndexRecordOption::WithFreqs))
+        .set_stored();
+    let text_field = schema_builder.add_text_field("text", text_options);
+    let id_field = schema_builder.add_text_field("id", STRING | STORED);
+    let index = Index::create_in_ram(schema_builder.build());
+    index.tokenizers().register(TOKENIZER_NAME, TextAnalyzer::from(CjkTokenizer));
+
+    let mut writer = index.writer_with_num_threads(1, 15_000_000)?;
+    let mut count = 0;
+    for (path, _, _) in files {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("[knowledge::build_index] skip {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let name = path.strip_prefix(folder).unwrap_or(path).to_string_lossy().to_string();
+        for (i, chunk) in chunk(&text, chunk_chars).into_iter().enumerate() {
+            writer.add_document(doc!(
+                id_field => format!("{}#{}", name, i),
+                text_field => chunk,
+            ))?;
+            count += 1;
+        }
+    }
+    writer.commit()?;
+    let reader = index.reader()?;
+    Ok((KnowledgeIndex { reader, id_field, text_field }, count))
+}
+
+// 轮询知识库目录, 文件有增删改时整体重建索引; 配置修改后下一轮生效
+pub fn start_watcher() {
+    std::thread::spawn(|| {
+        let mut last: Option<(String, Snapshot)> = None;
+        loop {
+            let config = config::get_config().unwrap().knowledge;
+            if config.folder.trim().is_empty() {
+                if last.take().is_some() {
+                    *KNOWLEDGE_INDEX.write().unwrap() = None;
+                }
+            } else {
+                let folder = PathBuf::from(&config.folder);
+                let mut files = Snapshot::new();
+                scan(&folder, &config.extensions, &mut files);
+                files.sort();
+                let changed = last.as_ref().map_or(true, |(f, s)| f != &config.folder || s != &files);
+                if changed {
+                    match build_index(&folder, &files, config.chunk_chars) {
+                        Ok((index, count)) => {
+                            *KNOWLEDGE_INDEX.write().unwrap() = Some(index);
+                            info!("[knowledge] indexed {} files, {} chunks", files.len(), count);
+                        }
+                        Err(e) => error!("[knowledge] failed to build index: {:?}", e),
+                    }
+                    last = Some((config.folder.clone(), files));
+                }
+            }
+            std::thread::sleep(Duration::from_secs(config.poll_interval.max(1)));
+        }
+    });
+}
+
+fn search(query: &str, top_k: usize) -> Result<Vec<SourceItem>, String> {
+    let guard = KNOWLEDGE_INDEX.read().unwrap();
+    let Some(index) = guard.as_ref() else { return Ok(Vec::new()) };
+    let Some(query) = token_query(index.text_field, query) else { return Ok(Vec::new()) };
+    let searcher = index.reader.searcher();
+    let top_docs = searcher.search(&query, &TopDocs::with_limit(top_k)).map_err(|e| e.to_string())?;
+    let mut items = Vec::new();
+    for (_, address) in top_docs {
+        let doc: TantivyDocument = searcher.doc(address).map_err(|e| e.to_string())?;
+        let field = |f: Field| doc.get_first(f).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        items.push(SourceItem { id: field(index.id_field), text: field(index.text_field), timestamp: None, origin: None, pinned: false, nearby_kind: None });
+    }
+    Ok(items)
+}
+
+pub struct KnowledgeSource;
+
+impl ContextSource for KnowledgeSource {
+    fn name(&self) -> &'static str {
+        "knowledge"
+    }
+
+    fn priority(&self) -> i32 {
+        8
+    }
+
+    fn budget_hint(&self) -> Option<SectionBudget> {
+        Some(SectionBudget { max_tokens: 600, min_items: 1 })
+    }
+
+    fn fetch<'a>(&'a self, input: &'a FocusedInput) -> BoxFuture<'a, Result<Vec<SourceItem>, String>> {
+        Box::pin(async move {
+            let top_k = config::get_config().unwrap().knowledge.top_k;
+            let content: Vec<char> = input.input_element.content.chars().collect();
+            let tail: String = content[content.len().saturating_sub(QUERY_TAIL_CHARS)..].iter().collect();
+            search(&format!("{} {}", tail, input.window_element.title), top_k)
+        })
+    }
+
+    fn render(&self, items: &[SourceItem]) -> String {
+        items
+            .iter()
+            .map(|item| format!("[{}]\n{}", item.id, item.text))
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+}
@@ -0,0 +1,109 @@
+//! 可插拔的上下文来源, 每个来源对应 prompt 中的一个同名模板变量({{name}})
+//! 新增来源: 在本目录实现 ContextSource, 再加入 SOURCES 列表
+
+mod clipboard;
+mod datetime;
+mod history;
+pub mod knowledge;
+mod screen;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::{join_all, BoxFuture};
+use log::{error, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::config::context::SectionBudget;
+use crate::context::InputContext;
+use crate::os::element::nearby::NearbyKind;
+use crate::os::element::ui_automation::FocusedInput;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceItem {
+    pub id: String,
+    pub text: String,
+    pub timestamp: Option<i64>,
+    // 产生这条内容的窗口/输入框, 用于打分和渲染
+    pub origin: Option<InputContext>,
+    // 置顶的条目排在最前, 在分区预算内优先保留
+    pub pinned: bool,
+    // 界面文本的来源位置, 只有 screen_context 使用
+    pub nearby_kind: Option<NearbyKind>,
+}
+
+pub trait ContextSource: Send + Sync {
+    /// 模板变量名
+    fn name(&self) -> &'static str;
+
+    /// 优先级高的来源先占用总 token 预算
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(500)
+    }
+
+    /// 默认预算, 可被 [context.budgets.<name>] 覆盖; None 表示不打分, 全部保留
+    fn budget_hint(&self) -> Option<SectionBudget> {
+        None
+    }
+
+    fn fetch<'a>(&'a self, input: &'a FocusedInput) -> BoxFuture<'a, Result<Vec<SourceItem>, String>>;
+
+    /// 把选中的条目渲染为模板变量的值, 默认每条一行
+    fn render(&self, items: &[SourceItem]) -> String {
+        items.iter().map(|i| i.text.as_str()).collect::<Vec<&str>>().join("\n")
+    }
+}
+
+static SOURCES: Lazy<Vec<Arc<dyn ContextSource>>> = Lazy::new(|| {
+    let mut sources: Vec<Arc<dyn ContextSource>> = vec![
+        Arc::new(history::HistorySource),
+        Arc::new(knowledge::KnowledgeSource),
+        Arc::new(screen::ScreenSource),
+        Arc::new(clipboard::ClipboardSource),
+        Arc::new(datetime::DateTimeSource),
+    ];
+    sources.sort_by_key(|s| -s.priority());
+    sources
+});
+
+pub struct FetchedSource {
+    pub source: Arc<dyn ContextSource>,
+    pub items: Vec<SourceItem>,
+}
+
+pub fn placeholder(name: &str) -> String {
+    format!("{{{{{}}}}}", name)
+}
+
+// 并发拉取模板中用到的来源, 出错或超时的来源按空处理
+pub async fn fetch_all(input: &FocusedInput, template: &str) -> Vec<FetchedSource> {
+    let sources: Vec<Arc<dyn ContextSource>> = SOURCES
+        .iter()
+        .filter(|s| template.contains(&placeholder(s.name())))
+        .cloned()
+        .collect();
+    let results = join_all(sources.iter().map(|source| async move {
+        match tokio::time::timeout(source.timeout(), source.fetch(input)).await {
+            Ok(Ok(items)) => items,
+            Ok(Err(e)) => {
+                error!("[fetch_all] source {} failed: {}", source.name(), e);
+                Vec::new()
+            }
+            Err(_) => {
+                warn!("[fetch_all] source {} timed out after {:?}", source.name(), source.timeout());
+                Vec::new()
+            }
+        }
+    }))
+    .await;
+    sources
+        .into_iter()
+        .zip(results)
+        .map(|(source, items)| FetchedSource { source, items })
+        .collect()
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+
+use crate::config::context::SectionBudget;
+use crate::context::outbound::format_screen_context;
+use crate::os::element::nearby::NearbyKind;
+use crate::os::element::ui_automation::FocusedInput;
+
+use super::{ContextSource, SourceItem};
+
+// 输入框周围的界面文本; 收集时已由 ui_automation.nearby_* 限制, 再按分区预算打分取舍
+// 标签和帮助文本置顶, 总在预算内优先保留
+pub struct ScreenSource;
+
+// 渲染时只标注标签和帮助文本, 兄弟元素和面板文本不加前缀
+fn kind_tag(kind: NearbyKind) -> Option<&'static str> {
+    match kind {
+        NearbyKind::Label => Some("label"),
+        NearbyKind::HelpText => Some("hint"),
+        NearbyKind::Sibling | NearbyKind::Pane => None,
+    }
+}
+
+impl ContextSource for ScreenSource {
+    fn name(&self) -> &'static str {
+        "screen_context"
+    }
+
+    fn priority(&self) -> i32 {
+        7
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(1000)
+    }
+
+    fn budget_hint(&self) -> Option<SectionBudget> {
+        Some(SectionBudget { max_tokens: 400, min_items: 2 })
+    }
+
+    // nearby 在获取聚焦输入框时从同一个元素收集, 焦点移走后也不会读到其它元素
+    // id 为原文顺序中的位置; 候选按由近及远给出, 得分相同时预算先给离输入框近的文本
+    fn fetch<'a>(&'a self, input: &'a FocusedInput) -> BoxFuture<'a, Result<Vec<SourceItem>, String>> {
+        Box::pin(async move {
+            Ok(input
+                .nearby
+                .clone()
+                .into_iter()
+                .enumerate()
+                .rev()
+                .map(|(i, t)| SourceItem {
+                    id: i.to_string(),
+                    text: t.text,
+                    timestamp: None,
+                    origin: None,
+                    pinned: matches!(t.kind, NearbyKind::Label | NearbyKind::HelpText),
+                    nearby_kind: Some(t.kind),
+                })
+                .collect())
+        })
+    }
+
+    // 按原文顺序输出, 离输入框最近的在最后
+    fn render(&self, items: &[SourceItem]) -> String {
+        let mut items: Vec<&SourceItem> = items.iter().collect();
+        items.sort_by_key(|item| item.id.parse::<usize>().unwrap_or(usize::MAX));
+        let texts: Vec<(Option<&str>, &str)> = items
+            .iter()
+            .map(|item| (item.nearby_kind.and_then(kind_tag), item.text.as_str()))
+            .collect();
+        format_screen_context(&texts)
+    }
+}
@@ -1,11 +1,13 @@
-use diesel::{connection::SimpleConnection, prelude::*};
-use log::info;
+use diesel::prelude::*;
+
+use crate::config::{self, ai_client::{AiClientConfig, AiProvider}};
+use crate::utils::hash::salted_hash;
 
 // ai_token_usage 表结构和 schema
 
 table! {
-    ai_token_usage (apikey) {
-        apikey -> Text,
+    ai_token_usage (profile_id) {
+        profile_id -> Text,
         used_token -> BigInt,
     }
 }
@@ -13,43 +15,49 @@ table! {
 #[derive(Queryable, Insertable, AsChangeset, Debug, Clone)]
 #[diesel(table_name = ai_token_usage)]
 pub struct AiTokenUsage {
-    pub apikey: String,
+    pub profile_id: String,
     pub used_token: i64,
 }
 
-pub fn ensure_ai_token_usage_table(conn: &mut SqliteConnection) {
-    conn.batch_execute(r#"
-        CREATE TABLE IF NOT EXISTS ai_token_usage (
-            apikey TEXT PRIMARY KEY,
-            used_token INTEGER
-        )
-    "#).expect("Failed to create ai_token_usage table");
+// 用量按提供方配置区分, 只保存 api_key(或 cmd)的加盐哈希, 密钥不入库也不经过前端
+pub fn api_profile_id(api_key: &str) -> String {
+    format!("api:{}", salted_hash(api_key))
+}
+
+pub fn profile_id(config: &AiClientConfig) -> String {
+    match config.provider {
+        AiProvider::API => api_profile_id(&config.api_key),
+        AiProvider::CMD => format!("cmd:{}", salted_hash(&config.cmd)),
+    }
 }
 
-pub fn get_used_token(conn: &mut SqliteConnection, apikey: &str) -> i64 {
+pub fn current_profile_id() -> String {
+    profile_id(&config::get_config().unwrap().ai_client)
+}
+
+pub fn get_used_token(conn: &mut SqliteConnection, profile_id_: &str) -> i64 {
     use self::ai_token_usage::dsl::*;
-    ensure_ai_token_usage_table(conn);
     ai_token_usage
-        .filter(apikey.eq(apikey))
+        .filter(profile_id.eq(profile_id_))
         .select(used_token)
         .first::<i64>(conn)
         .unwrap_or(0)
 }
 
-pub fn set_used_token(conn: &mut SqliteConnection, apikey_: &str, value: i64) {
+pub fn increment_used_token(conn: &mut SqliteConnection, profile_id_: &str, delta: i64) -> QueryResult<usize> {
     use self::ai_token_usage::dsl::*;
-    ensure_ai_token_usage_table(conn);
-    let usage = AiTokenUsage { apikey: apikey_.to_string(), used_token: value };
-    diesel::replace_into(ai_token_usage).values(&usage).execute(conn).ok();
+    let usage = AiTokenUsage { profile_id: profile_id_.to_string(), used_token: delta };
+    diesel::insert_into(ai_token_usage)
+        .values(&usage)
+        .on_conflict(profile_id)
+        .do_update()
+        .set(used_token.eq(used_token + delta))
+        .execute(conn)
 }
 
-pub fn increment_used_token(conn: &mut SqliteConnection, apikey_: &str, delta: i64) {
-    let current = get_used_token(conn, apikey_);
-    set_used_token(conn, apikey_, current + delta);
-} 
-    
+// 当前提供方配置的用量
 #[tauri::command]
-pub fn get_used_token_command(apikey: String) -> i64 {
-    let mut conn = crate::db::conn::establish_connection();
-    crate::db::ai_token_usage::get_used_token(&mut conn, &apikey)
-}
\ No newline at end of file
+pub async fn get_used_token_command() -> Result<i64, String> {
+    let profile = current_profile_id();
+    crate::db::conn::with_conn(move |conn| get_used_token(conn, &profile)).await
+}
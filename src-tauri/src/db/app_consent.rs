@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use diesel::prelude::*;
+use log::{error, info};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::db::conn::with_conn;
+
+// app_consent 表结构和 schema: 每个应用首次触发补全时用户的授权决定
+
+table! {
+    app_consent (window_app) {
+        window_app -> Text,
+        decision -> Text,
+        timestamp -> BigInt,
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ConsentDecision {
+    Allow,
+    Deny,
+    // 允许, 但只能使用本地模型
+    LocalOnly,
+}
+
+impl ConsentDecision {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConsentDecision::Allow => "Allow",
+            ConsentDecision::Deny => "Deny",
+            ConsentDecision::LocalOnly => "LocalOnly",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Allow" => Some(ConsentDecision::Allow),
+            "Deny" => Some(ConsentDecision::Deny),
+            "LocalOnly" => Some(ConsentDecision::LocalOnly),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Queryable, Insertable, AsChangeset, Debug, Clone)]
+#[diesel(table_name = app_consent)]
+struct AppConsentRow {
+    window_app: String,
+    decision: String,
+    timestamp: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AppConsent {
+    pub window_app: String,
+    pub decision: ConsentDecision,
+    pub timestamp: i64,
+}
+
+// 授权决定缓存, 启动时打开数据库后加载一次, 焦点循环和写线程只读缓存, 不占用连接
+static CONSENT_CACHE: Lazy<RwLock<HashMap<String, ConsentDecision>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub fn list_consents(conn: &mut SqliteConnection) -> QueryResult<Vec<AppConsent>> {
+    let rows = app_consent::table.load::<AppConsentRow>(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| ConsentDecision::parse(&r.decision).map(|decision| AppConsent {
+            window_app: r.window_app,
+            decision,
+            timestamp: r.timestamp,
+        }))
+        .collect())
+}
+
+// 在 init_pool 之后调用
+pub fn load_cache(conn: &mut SqliteConnection) {
+    match list_consents(conn) {
+        Ok(consents) => {
+            *CONSENT_CACHE.write().unwrap() = consents.into_iter().map(|c| (c.window_app, c.decision)).collect();
+        }
+        Err(e) => error!("[load_cache] failed to load app consents: {}", e),
+    }
+}
+
+pub fn get_consent(app: &str) -> Option<ConsentDecision> {
+    CONSENT_CACHE.read().unwrap().get(app).copied()
+}
+
+pub fn is_denied(app: &str) -> bool {
+    get_consent(app) == Some(ConsentDecision::Deny)
+}
+
+// 拒绝授权的应用: 不保存其输入历史, 也不作为其它应用的上下文; 在调用方的连接上读取
+pub fn denied_apps(conn: &mut SqliteConnection) -> QueryResult<Vec<String>> {
+    use self::app_consent::dsl::*;
+    app_consent
+        .filter(decision.eq(ConsentDecision::Deny.as_str()))
+        .select(window_app)
+        .load::<String>(conn)
+}
+
+pub fn set_consent(conn: &mut SqliteConnection, app: &str, decision: ConsentDecision) {
+    let row = AppConsentRow {
+        window_app: app.to_string(),
+        decision: decision.as_str().to_string(),
+        timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64,
+    };
+    if let Err(e) = diesel::replace_into(app_consent::table).values(&row).execute(conn) {
+        error!("Failed to save app consent: {}", e);
+        return;
+    }
+    CONSENT_CACHE.write().unwrap().insert(app.to_string(), decision);
+}
+
+pub fn delete_consent(conn: &mut SqliteConnection, app: &str) {
+    use self::app_consent::dsl::*;
+    if let Err(e) = diesel::delete(app_consent.filter(window_app.eq(app))).execute(conn) {
+        error!("Failed to delete app consent: {}", e);
+        return;
+    }
+    CONSENT_CACHE.write().unwrap().remove(app);
+}
+
+#[tauri::command]
+pub async fn set_app_consent_command(app: String, decision: ConsentDecision) -> Result<(), String> {
+    info!("[set_app_consent_command] app: {}, decision: {:?}", app, decision);
+    let app = with_conn(move |conn| {
+        set_consent(conn, &app, decision);
+        app
+    })
+    .await?;
+    crate::input::on_consent_decided(&app, decision);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_app_consents_command() -> Result<Vec<AppConsent>, String> {
+    with_conn(list_consents).await?.map_err(|e| e.to_string())
+}
+
+// 删除后该应用下次触发补全时会重新询问
+#[tauri::command]
+pub async fn delete_app_consent_command(app: String) -> Result<(), String> {
+    with_conn(move |conn| delete_consent(conn, &app)).await
+}
@@ -0,0 +1,326 @@
+//! 导出/导入输入历史、用量和当前配置, 用于迁移到另一台机器
+//! 归档为 zip: manifest.json(格式版本、数据库结构版本等), input.jsonl, ai_token_usage.jsonl, config.toml
+//! 默认不导出密钥: 配置中的 api_key 置空, 用量只按 profile id(本机加盐)导出, 只能在导出它的机器上对应回配置;
+//! 包含密钥时当前配置的用量另附 api_key 的哈希, 导入时与另一台机器上配置的 api_key 比对
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::ai::privacy::redact_for_storage;
+use crate::config::{self, Config};
+use crate::context::history::{retention, search};
+use crate::db::app_consent;
+use crate::db::ai_token_usage::{ai_token_usage, api_profile_id, current_profile_id, AiTokenUsage};
+use crate::db::input_history::{input, Input};
+use crate::utils::hash::sha256_hex;
+
+use super::conn::{establish_connection, with_conn};
+use super::migrations;
+
+// 归档格式版本, 文件布局变化时递增; 2: 用量改为按 profile id 导出
+const FORMAT_VERSION: u32 = 2;
+const MANIFEST: &str = "manifest.json";
+const INPUT_FILE: &str = "input.jsonl";
+const USAGE_FILE: &str = "ai_token_usage.jsonl";
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub schema_version: i32,
+    pub app_version: String,
+    pub exported_at: i64,
+    pub includes_secrets: bool,
+    pub input_count: usize,
+    pub usage_count: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct UsageRecord {
+    // 加盐哈希, 只在导出它的机器上有意义
+    #[serde(default)]
+    profile_id: Option<String>,
+    // 当前配置的 api_key 的哈希(不加盐), 用于在另一台机器上与其配置的 api_key 比对; 可被离线比对, 只在包含密钥时导出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    apikey_hash: Option<String>,
+    // 1 版归档在包含密钥时导出的明文 apikey, 只读不写
+    #[serde(default, skip_serializing)]
+    apikey: Option<String>,
+    used_token: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub duplicates: usize,
+    // 按 never_store 或拒绝授权跳过的记录
+    pub filtered: usize,
+    pub usage_merged: usize,
+    pub usage_skipped: usize,
+    pub config_imported: bool,
+}
+
+fn now_millis() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64
+}
+
+fn write_jsonl<T: Serialize, W: Write>(writer: &mut W, records: &[T]) -> Result<(), String> {
+    for record in records {
+        let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", line).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn read_jsonl<T: for<'de> Deserialize<'de>, R: Read>(reader: R, name: &str) -> Result<Vec<T>, String> {
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| format!("{} line {}: {}", name, i + 1, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+pub fn export(conn: &mut SqliteConnection, path: &str, include_secrets: bool) -> Result<Manifest, String> {
+    let inputs = input::table.order(input::timestamp.asc()).load::<Input>(conn).map_err(|e| e.to_string())?;
+    let mut config = config::get_config().ok_or("config is not loaded")?;
+    let local_key = config.ai_client.api_key.clone();
+    let usage: Vec<UsageRecord> = ai_token_usage::table
+        .load::<AiTokenUsage>(conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|u| UsageRecord {
+            apikey_hash: (include_secrets && !local_key.is_empty() && u.profile_id == api_profile_id(&local_key))
+                .then(|| sha256_hex(local_key.as_bytes())),
+            profile_id: Some(u.profile_id),
+            apikey: None,
+            used_token: u.used_token,
+        })
+        .collect();
+    if !include_secrets {
+        config.ai_client.api_key = String::new();
+    }
+    let config_str = toml::to_string_pretty(&config).map_err(|e| e.to_string())?;
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        schema_version: migrations::current_version(conn).map_err(|e| e.to_string())?,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: now_millis(),
+        includes_secrets: include_secrets,
+        input_count: inputs.len(),
+        usage_count: usage.len(),
+    };
+
+    let file = File::create(path).map_err(|e| format!("failed to create {}: {}", path, e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(MANIFEST, options).map_err(|e| e.to_string())?;
+    zip.write_all(serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?.as_bytes()).map_err(|e| e.to_string())?;
+    zip.start_file(INPUT_FILE, options).map_err(|e| e.to_string())?;
+    write_jsonl(&mut zip, &inputs)?;
+    zip.start_file(USAGE_FILE, options).map_err(|e| e.to_string())?;
+    write_jsonl(&mut zip, &usage)?;
+    zip.start_file(CONFIG_FILE, options).map_err(|e| e.to_string())?;
+    zip.write_all(config_str.as_bytes()).map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+
+    info!("[archive::export] exported {} history entries and {} usage records to {}", manifest.input_count, manifest.usage_count, path);
+    Ok(manifest)
+}
+
+// 与 save_history 相同的过滤: 不保存 never_store 和拒绝授权的应用
+// 按 (时间, 内容) 去重; id 冲突但内容不同时换一个新 id
+fn import_inputs(conn: &mut SqliteConnection, records: Vec<Input>) -> QueryResult<(usize, usize, usize)> {
+    use crate::db::input_history::input::dsl::*;
+    let denied = app_consent::denied_apps(conn)?;
+    conn.transaction(|conn| {
+        let (mut inserted, mut duplicates, mut filtered) = (0, 0, 0);
+        for mut record in records {
+            if denied.contains(&record.window_app) || retention::never_store(&record.window_app, &record.window_title) {
+                filtered += 1;
+                continue;
+            }
+            record.window_title = redact_for_storage(&record.window_title);
+            record.input_content = redact_for_storage(&record.input_content);
+            let exists = input
+                .filter(timestamp.eq(record.timestamp))
+                .filter(input_content.eq(&record.input_content))
+                .count()
+                .get_result::<i64>(conn)?;
+            if exists > 0 {
+                duplicates += 1;
+                continue;
+            }
+            let base_id = record.id.clone();
+            let mut suffix = 1;
+            while input.find(&record.id).count().get_result::<i64>(conn)? > 0 {
+                record.id = format!("{}-{}", base_id, suffix);
+                suffix += 1;
+            }
+            diesel::insert_into(input).values(&record).execute(conn)?;
+            inserted += 1;
+        }
+        Ok((inserted, duplicates, filtered))
+    })
+}
+
+// 用量取两边的较大值, 重复导入同一归档不会重复累加
+fn import_usage(conn: &mut SqliteConnection, records: Vec<UsageRecord>) -> QueryResult<(usize, usize)> {
+    use crate::db::ai_token_usage::ai_token_usage::dsl::*;
+    let local_key = config::get_config().map(|c| c.ai_client.api_key).unwrap_or_default();
+    let local_hash = sha256_hex(local_key.as_bytes());
+    let local_profile = current_profile_id();
+    conn.transaction(|conn| {
+        let (mut merged, mut skipped) = (0, 0);
+        for record in records {
+            let target = if record.profile_id.as_deref() == Some(local_profile.as_str()) {
+                Some(local_profile.clone())
+            } else if !local_key.is_empty() && record.apikey_hash.as_deref() == Some(local_hash.as_str()) {
+                Some(api_profile_id(&local_key))
+            } else if let Some(key) = &record.apikey {
+                Some(api_profile_id(key))
+            } else {
+                // 同一台机器上的其它配置
+                record.profile_id.clone()
+            };
+            let Some(target) = target else {
+                skipped += 1;
+                continue;
+            };
+            let existing = ai_token_usage.find(&target).select(used_token).first::<i64>(conn).optional()?.unwrap_or(0);
+            if record.used_token > existing {
+                diesel::replace_into(ai_token_usage)
+                    .values(&AiTokenUsage { profile_id: target, used_token: record.used_token })
+                    .execute(conn)?;
+            }
+            merged += 1;
+        }
+        Ok((merged, skipped))
+    })
+}
+
+// 本机的存储设置(加密、密钥文件)不随配置导入; 归档不含密钥时保留本机的 api_key
+fn import_config(imported: Config, includes_secrets: bool) -> Result<(), String> {
+    let local = config::get_config().ok_or("config is not loaded")?;
+    let mut imported = imported;
+    imported.storage = local.storage;
+    if !includes_secrets {
+        imported.ai_client.api_key = local.ai_client.api_key;
+    }
+    config::save_config_for_frontend(imported);
+    Ok(())
+}
+
+pub fn import(conn: &mut SqliteConnection, path: &str, with_config: bool) -> Result<ImportSummary, String> {
+    let file = File::open(path).map_err(|e| format!("failed to open {}: {}", path, e))?;
+    let mut zip = ZipArchive::new(file).map_err(|e| format!("{} is not a valid archive: {}", path, e))?;
+
+    let manifest: Manifest = serde_json::from_reader(zip.by_name(MANIFEST).map_err(|e| format!("{}: {}", MANIFEST, e))?)
+        .map_err(|e| format!("invalid {}: {}", MANIFEST, e))?;
+    if manifest.format_version == 0 || manifest.format_version > FORMAT_VERSION {
+        return Err(format!("unsupported archive format version {}", manifest.format_version));
+    }
+    let schema_version = migrations::current_version(conn).map_err(|e| e.to_string())?;
+    if manifest.schema_version > schema_version {
+        return Err(format!(
+            "archive schema version {} is newer than this database ({}), please upgrade ainput first",
+            manifest.schema_version, schema_version
+        ));
+    }
+
+    let inputs: Vec<Input> = read_jsonl(zip.by_name(INPUT_FILE).map_err(|e| format!("{}: {}", INPUT_FILE, e))?, INPUT_FILE)?;
+    if inputs.len() != manifest.input_count {
+        return Err(format!("{} has {} records, manifest says {}", INPUT_FILE, inputs.len(), manifest.input_count));
+    }
+    let usage: Vec<UsageRecord> = read_jsonl(zip.by_name(USAGE_FILE).map_err(|e| format!("{}: {}", USAGE_FILE, e))?, USAGE_FILE)?;
+    let imported_config = if with_config {
+        let mut config_str = String::new();
+        zip.by_name(CONFIG_FILE)
+            .map_err(|e| format!("{}: {}", CONFIG_FILE, e))?
+            .read_to_string(&mut config_str)
+            .map_err(|e| e.to_string())?;
+        Some(toml::from_str::<Config>(&config_str).map_err(|e| format!("invalid {}: {}", CONFIG_FILE, e))?)
+    } else {
+        None
+    };
+
+    let (inserted, duplicates, filtered) = import_inputs(conn, inputs).map_err(|e| e.to_string())?;
+    let (usage_merged, usage_skipped) = import_usage(conn, usage).map_err(|e| e.to_string())?;
+    let config_imported = match imported_config {
+        Some(config) => {
+            import_config(config, manifest.includes_secrets)?;
+            true
+        }
+        None => false,
+    };
+    let summary = ImportSummary { inserted, duplicates, filtered, usage_merged, usage_skipped, config_imported };
+    info!("[archive::import] imported {}: {:?}", path, summary);
+    Ok(summary)
+}
+
+#[tauri::command]
+pub async fn export_data_command(path: String, include_secrets: Option<bool>) -> Result<Manifest, String> {
+    with_conn(move |conn| export(conn, &path, include_secrets.unwrap_or(false))).await?
+}
+
+#[tauri::command]
+pub async fn import_data_command(path: String, import_config: Option<bool>) -> Result<ImportSummary, String> {
+    let summary = with_conn(move |conn| import(conn, &path, import_config.unwrap_or(false))).await??;
+    if summary.inserted > 0 {
+        if let Err(e) = tokio::task::spawn_blocking(search::rebuild_from_db).await.map_err(|e| e.to_string())? {
+            warn!("[archive::import] failed to rebuild search index: {}", e);
+        }
+    }
+    Ok(summary)
+}
+
+/// 命令行: --export <path> [--include-secrets] 或 --import <path> [--import-config], 完成后退出
+/// 返回 None 表示没有相关参数, 否则为进程退出码
+/// release 版本为 GUI 子系统, 没有自己的控制台, 结果输出到启动它的控制台, 同时写入日志
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    let value_of = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).cloned();
+    let has = |flag: &str| args.iter().any(|a| a == flag);
+    let result = if let Some(path) = value_of("--export") {
+        let mut conn = establish_connection();
+        export(&mut conn, &path, has("--include-secrets"))
+            .map(|m| format!("exported {} history entries and {} usage records to {}", m.input_count, m.usage_count, path))
+    } else if let Some(path) = value_of("--import") {
+        // 索引写锁可能被正在运行的 GUI 持有, 命令行不打开索引, 只标记由 GUI 下次启动时重建
+        let mut conn = establish_connection();
+        import(&mut conn, &path, has("--import-config")).map(|s| {
+            if s.inserted > 0 {
+                search::mark_stale();
+            }
+            format!("{:?}", s)
+        })
+    } else {
+        return None;
+    };
+    // 从资源管理器启动时没有父控制台, 只写日志
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+    match result {
+        Ok(message) => {
+            info!("[archive::run_cli] {}", message);
+            println!("[archive] {}", message);
+            Some(0)
+        }
+        Err(e) => {
+            error!("[archive::run_cli] failed: {}", e);
+            println!("[archive] failed: {}", e);
+            Some(1)
+        }
+    }
+}
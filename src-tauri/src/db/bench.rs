@@ -0,0 +1,176 @@
+//! 历史检索基准, 只在测试中编译:
+//! - `query_plans_use_indexes` 检查各查询的查询计划, 出现全表扫描或临时 B 树排序即失败
+//! - `get_history_latency` 在临时数据库中生成合成历史(默认 50 万行, 可用 AINPUT_BENCH_ROWS 覆盖), 平均耗时超过阈值即失败;
+//!   耗时较长, 默认忽略: `cargo test bench -- --ignored --nocapture`
+
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use diesel::sqlite::SqliteConnection;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::context::history::history::get_history;
+use crate::db::input_history::{input, Input};
+
+const DEFAULT_ROWS: usize = 500_000;
+// 查询计划检查只需少量数据
+const PLAN_ROWS: usize = 2_000;
+const BENCH_DB: &str = "bench_history.db";
+const APPS: usize = 200;
+const WINDOWS: usize = 5_000;
+const INPUTS_PER_WINDOW: usize = 5;
+const QUERIES: usize = 200;
+const CANDIDATE_LIMIT: i64 = 30;
+const INSERT_CHUNK: usize = 1_000;
+const MAX_AVG: Duration = Duration::from_millis(20);
+
+#[derive(QueryableByName)]
+struct PlanRow {
+    #[diesel(sql_type = Text)]
+    detail: String,
+}
+
+fn synthetic_row(rng: &mut StdRng, i: usize, now: i64) -> Input {
+    let window = rng.gen_range(0..WINDOWS);
+    let field = rng.gen_range(0..INPUTS_PER_WINDOW);
+    Input {
+        id: format!("bench-{}", i),
+        window_id: format!("{}", 100_000 + window),
+        window_app: format!("app{}.exe", window % APPS),
+        window_title: format!("window {}", window),
+        window_class_name: "BenchWindow".to_string(),
+        window_x: 0,
+        window_y: 0,
+        window_width: 800,
+        window_height: 600,
+        input_id: format!("input{}", field),
+        input_title: format!("field {}", field),
+        input_control_type: 50004,
+        input_x: 10,
+        input_y: 10,
+        input_width: 300,
+        input_height: 24,
+        input_content: format!("synthetic message {} in window {} field {}", i, window, field),
+        // 分布在最近 180 天内
+        timestamp: now - rng.gen_range(0..180 * 24 * 3600 * 1000i64),
+        pinned: false,
+    }
+}
+
+fn populate(conn: &mut SqliteConnection, rows: usize) -> QueryResult<()> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
+    conn.transaction(|conn| {
+        let mut start = 0;
+        while start < rows {
+            let end = (start + INSERT_CHUNK).min(rows);
+            let chunk: Vec<Input> = (start..end).map(|i| synthetic_row(&mut rng, i, now)).collect();
+            diesel::insert_into(input::table).values(&chunk).execute(conn)?;
+            start = end;
+        }
+        Ok(())
+    })
+}
+
+// 与 get_history 和 save_history 中的查询形状一致
+const PLANNED_QUERIES: &[(&str, &str)] = &[
+    ("same field", "SELECT * FROM input WHERE window_id = '100001' AND input_id = 'input1' ORDER BY timestamp DESC LIMIT 30"),
+    ("same field, new handle", "SELECT * FROM input WHERE window_title = 'window 1' AND window_id != '1' AND input_title = 'field 1' ORDER BY timestamp DESC LIMIT 30"),
+    ("same window", "SELECT * FROM input WHERE window_id = '100001' AND input_id != 'input1' ORDER BY timestamp DESC LIMIT 30"),
+    ("same window, new handle", "SELECT * FROM input WHERE window_title = 'window 1' AND window_id != '1' AND input_title != 'field 1' ORDER BY timestamp DESC LIMIT 30"),
+    ("same app", "SELECT * FROM input WHERE window_app = 'app1.exe' AND window_title != 'window 1' ORDER BY timestamp DESC LIMIT 30"),
+    ("other apps", "SELECT * FROM input WHERE window_app != 'app1.exe' ORDER BY timestamp DESC LIMIT 30"),
+    ("last draft", "SELECT * FROM input WHERE window_app = 'app1.exe' AND ((window_id = '100001' AND input_id = 'input1') OR (window_title = 'window 1' AND input_title = 'field 1')) ORDER BY timestamp DESC LIMIT 1"),
+    ("pinned", "SELECT * FROM input WHERE pinned = 1 ORDER BY timestamp DESC LIMIT 30"),
+    ("retention", "SELECT id, window_app, window_title, timestamp FROM input WHERE pinned = 0 AND timestamp < 0"),
+];
+
+// 返回退化的查询: 不带索引的全表扫描, 或需要临时 B 树排序
+fn check_plans(conn: &mut SqliteConnection) -> QueryResult<Vec<&'static str>> {
+    let mut degraded = Vec::new();
+    for (name, sql) in PLANNED_QUERIES {
+        let plan: Vec<PlanRow> = diesel::sql_query(format!("EXPLAIN QUERY PLAN {}", sql)).load(conn)?;
+        let detail: Vec<String> = plan.into_iter().map(|r| r.detail).collect();
+        let full_scan = detail.iter().any(|d| d.starts_with("SCAN") && !d.contains("INDEX"));
+        let temp_sort = detail.iter().any(|d| d.contains("USE TEMP B-TREE"));
+        if full_scan || temp_sort {
+            degraded.push(*name);
+        }
+        let note = match (full_scan, temp_sort) {
+            (true, _) => "  <-- full scan",
+            (_, true) => "  <-- temp b-tree sort",
+            _ => "",
+        };
+        println!("[plan] {:<24} {}{}", name, detail.join(" | "), note);
+    }
+    Ok(degraded)
+}
+
+fn bench_queries(conn: &mut SqliteConnection) -> QueryResult<Vec<Duration>> {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut timings = Vec::with_capacity(QUERIES);
+    for _ in 0..QUERIES {
+        let window = rng.gen_range(0..WINDOWS);
+        let field = rng.gen_range(0..INPUTS_PER_WINDOW);
+        let started = Instant::now();
+        get_history(
+            conn,
+            &format!("{}", 100_000 + window),
+            &format!("app{}.exe", window % APPS),
+            &format!("window {}", window),
+            &format!("input{}", field),
+            &format!("field {}", field),
+            "",
+            CANDIDATE_LIMIT,
+        )?;
+        timings.push(started.elapsed());
+    }
+    timings.sort();
+    Ok(timings)
+}
+
+fn migrated_conn(path: &str) -> SqliteConnection {
+    static DATA_DIR: std::sync::Once = std::sync::Once::new();
+    DATA_DIR.call_once(|| {
+        let dir = std::env::temp_dir().join("ainput-bench-test");
+        let _ = crate::utils::data_dir::init(&dir.to_string_lossy());
+    });
+    let mut conn = SqliteConnection::establish(path).unwrap();
+    super::migrations::run(&mut conn).unwrap();
+    conn
+}
+
+#[test]
+fn query_plans_use_indexes() {
+    let mut conn = migrated_conn(":memory:");
+    populate(&mut conn, PLAN_ROWS).unwrap();
+    let degraded = check_plans(&mut conn).unwrap();
+    assert!(degraded.is_empty(), "degraded query plans: {:?}", degraded);
+}
+
+#[test]
+#[ignore]
+fn get_history_latency() {
+    let rows = std::env::var("AINPUT_BENCH_ROWS").ok().and_then(|r| r.parse().ok()).unwrap_or(DEFAULT_ROWS);
+    let path = std::env::temp_dir().join(BENCH_DB).to_string_lossy().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut conn = migrated_conn(&path);
+
+    let started = Instant::now();
+    populate(&mut conn, rows).unwrap();
+    println!("[bench] inserted {} rows in {:?}", rows, started.elapsed());
+
+    let degraded = check_plans(&mut conn).unwrap();
+    let timings = bench_queries(&mut conn).unwrap();
+    drop(conn);
+    let _ = std::fs::remove_file(&path);
+
+    let avg = timings.iter().sum::<Duration>() / timings.len() as u32;
+    let p95 = timings[timings.len() * 95 / 100];
+    let max = timings[timings.len() - 1];
+    println!("[bench] get_history x{}: avg {:?}, p95 {:?}, max {:?} (limit {:?})", QUERIES, avg, p95, max, MAX_AVG);
+    assert!(degraded.is_empty(), "degraded query plans: {:?}", degraded);
+    assert!(avg <= MAX_AVG, "get_history average {:?} above {:?}", avg, MAX_AVG);
+}
@@ -0,0 +1,138 @@
+use diesel::prelude::*;
+use log::error;
+use serde::Serialize;
+
+// completion_event 表结构和 schema: 每次补全请求一行, 记录展示了什么以及用户如何处理, 只保存在本地
+
+table! {
+    completion_event (id) {
+        id -> BigInt,
+        timestamp -> BigInt,
+        app -> Text,
+        provider -> Text,
+        model -> Text,
+        prompt_tokens -> BigInt,
+        first_token_ms -> Nullable<BigInt>,
+        total_ms -> Nullable<BigInt>,
+        shown_chars -> BigInt,
+        shown_text -> Text,
+        accepted_chars -> BigInt,
+        typed_chars -> BigInt,
+        full_accepts -> Integer,
+        partial_accepts -> Integer,
+        typed_through -> Bool,
+        dismissed -> Bool,
+        outcome -> Text,
+        error -> Nullable<Text>,
+    }
+}
+
+// 结果分类, 按优先级: 出错、整段采纳、部分采纳、照着候选输入、Esc 关闭、未展示、展示后未处理
+pub const OUTCOME_ERROR: &str = "error";
+pub const OUTCOME_ACCEPTED: &str = "accepted";
+pub const OUTCOME_PARTIAL: &str = "partial";
+pub const OUTCOME_TYPED_THROUGH: &str = "typed_through";
+pub const OUTCOME_DISMISSED: &str = "dismissed";
+pub const OUTCOME_EMPTY: &str = "empty";
+pub const OUTCOME_IGNORED: &str = "ignored";
+
+#[derive(Queryable, Serialize, Debug, Clone)]
+#[diesel(table_name = completion_event)]
+pub struct CompletionEvent {
+    pub id: i64,
+    pub timestamp: i64,
+    pub app: String,
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: i64,
+    // 请求开始到第一个字的耗时
+    pub first_token_ms: Option<i64>,
+    pub total_ms: Option<i64>,
+    pub shown_chars: i64,
+    // 展示的候选, 按 privacy.redact_on_store 脱敏
+    pub shown_text: String,
+    pub accepted_chars: i64,
+    // 请求期间输入框中新增的字数减去采纳的字数, 即手动输入的字数
+    pub typed_chars: i64,
+    pub full_accepts: i32,
+    pub partial_accepts: i32,
+    pub typed_through: bool,
+    pub dismissed: bool,
+    pub outcome: String,
+    pub error: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone, Default)]
+#[diesel(table_name = completion_event)]
+pub struct NewCompletionEvent {
+    pub timestamp: i64,
+    pub app: String,
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: i64,
+    pub first_token_ms: Option<i64>,
+    pub total_ms: Option<i64>,
+    pub shown_chars: i64,
+    pub shown_text: String,
+    pub accepted_chars: i64,
+    pub typed_chars: i64,
+    pub full_accepts: i32,
+    pub partial_accepts: i32,
+    pub typed_through: bool,
+    pub dismissed: bool,
+    pub outcome: String,
+    pub error: Option<String>,
+}
+
+impl NewCompletionEvent {
+    pub fn classify(&self) -> &'static str {
+        if self.error.is_some() {
+            OUTCOME_ERROR
+        } else if self.full_accepts > 0 {
+            OUTCOME_ACCEPTED
+        } else if self.partial_accepts > 0 {
+            OUTCOME_PARTIAL
+        } else if self.typed_through {
+            OUTCOME_TYPED_THROUGH
+        } else if self.dismissed {
+            OUTCOME_DISMISSED
+        } else if self.shown_chars == 0 {
+            OUTCOME_EMPTY
+        } else {
+            OUTCOME_IGNORED
+        }
+    }
+}
+
+pub fn insert_event(conn: &mut SqliteConnection, record: &NewCompletionEvent) {
+    if let Err(e) = diesel::insert_into(completion_event::table).values(record).execute(conn) {
+        error!("Failed to insert completion event: {}", e);
+    }
+}
+
+pub fn query_events(
+    conn: &mut SqliteConnection,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<i64>,
+) -> QueryResult<Vec<CompletionEvent>> {
+    use self::completion_event::dsl::*;
+    let mut query = completion_event.into_boxed();
+    if let Some(from) = from {
+        query = query.filter(timestamp.ge(from));
+    }
+    if let Some(to) = to {
+        query = query.filter(timestamp.lt(to));
+    }
+    if let Some(limit) = limit {
+        query = query.limit(limit);
+    }
+    query.order(timestamp.desc()).load::<CompletionEvent>(conn)
+}
+
+#[tauri::command]
+pub async fn get_completion_events_command(from: Option<i64>, to: Option<i64>, limit: Option<i64>) -> Result<Vec<CompletionEvent>, String> {
+    crate::db::conn::with_conn(move |conn| query_events(conn, from, to, limit))
+        .await?
+        .map_err(|e| e.to_string())
+}
@@ -1,29 +1,69 @@
-use diesel::{sql_query, sqlite::SqliteConnection, Connection, RunQueryDsl};
-
-pub fn establish_connection() -> SqliteConnection {
-    let mut conn = SqliteConnection::establish("input.db").expect("Error connecting to input.db");
-    // 自动建表 input
-    sql_query(r#"
-        CREATE TABLE IF NOT EXISTS input (
-            id TEXT PRIMARY KEY,
-            window_id TEXT,
-            window_app TEXT,
-            window_title TEXT,
-            window_class_name TEXT,
-            window_x INTEGER,
-            window_y INTEGER,
-            window_width INTEGER,
-            window_height INTEGER,
-            input_id TEXT,
-            input_title TEXT,
-            input_control_type INTEGER,
-            input_x INTEGER,
-            input_y INTEGER,
-            input_width INTEGER,
-            input_height INTEGER,
-            input_content TEXT,
-            timestamp BIGINT
-        );
-    "#).execute(&mut conn).expect("Failed to create input table");
-    conn
-} 
\ No newline at end of file
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, PooledConnection};
+use diesel::{sqlite::SqliteConnection, Connection};
+use once_cell::sync::OnceCell;
+
+const POOL_SIZE: u32 = 4;
+
+pub type DbConnection = PooledConnection<ConnectionManager<SqliteConnection>>;
+
+static POOL: OnceCell<Pool<ConnectionManager<SqliteConnection>>> = OnceCell::new();
+
+// 每个连接取出时解锁数据库, 并设置等锁时间避免与写线程冲突时直接报错
+#[derive(Debug)]
+struct ConnectionOptions;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        super::encryption::apply_key(conn)
+            .map_err(|e| diesel::r2d2::Error::QueryError(diesel::result::Error::QueryBuilderError(e.into())))?;
+        conn.batch_execute("PRAGMA busy_timeout = 5000; PRAGMA synchronous = NORMAL;")
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+// 数据目录下的 input.db
+pub fn db_path() -> String {
+    crate::utils::data_dir::path_str("input.db")
+}
+
+pub fn try_establish_connection() -> Result<SqliteConnection, String> {
+    let path = db_path();
+    let mut conn = SqliteConnection::establish(&path).map_err(|e| format!("failed to open {}: {}", path, e))?;
+    super::encryption::apply_key(&mut conn).map_err(|e| format!("failed to unlock {}: {}", path, e))?;
+    Ok(conn)
+}
+
+/// 启动时在迁移完成后调用一次: 开启 WAL 并创建连接池
+pub fn init_pool() -> Result<(), String> {
+    let mut conn = try_establish_connection()?;
+    conn.batch_execute("PRAGMA journal_mode = WAL;").map_err(|e| format!("failed to enable WAL: {}", e))?;
+    let pool = Pool::builder()
+        .max_size(POOL_SIZE)
+        .connection_customizer(Box::new(ConnectionOptions))
+        .build(ConnectionManager::<SqliteConnection>::new(db_path()))
+        .map_err(|e| format!("failed to create connection pool: {}", e))?;
+    POOL.set(pool).map_err(|_| "connection pool already initialized".to_string())
+}
+
+// 从连接池取连接; 会阻塞, 异步代码中请用 with_conn
+pub fn establish_connection() -> DbConnection {
+    POOL.get()
+        .expect("connection pool is not initialized")
+        .get()
+        .expect("Error connecting to input.db")
+}
+
+/// 在阻塞线程池中使用连接, 供异步命令和上下文来源读取数据
+pub async fn with_conn<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce(&mut SqliteConnection) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let mut conn = establish_connection();
+        f(&mut conn)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
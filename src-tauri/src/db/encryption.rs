@@ -0,0 +1,154 @@
+//! input.db 静态加密(SQLCipher): 密钥来自 keyfile 或口令, 已有明文数据库在启动时迁移为加密数据库
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use diesel::{connection::SimpleConnection, sqlite::SqliteConnection, Connection};
+use log::{error, info};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+
+use crate::config::{self, storage::Encryption};
+
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+// PRAGMA key 的取值, 启动时计算一次; None 表示不加密
+static KEY_LITERAL: Lazy<Result<Option<String>, String>> = Lazy::new(load_key_literal);
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// 相对路径的 keyfile 放在数据目录下; 不存在时生成新密钥, 但 db_path 已加密时报错, 否则新密钥无法打开已有数据
+fn load_keyfile(path: &str, db_path: &str) -> Result<String, String> {
+    let path = &crate::utils::data_dir::path_str(path);
+    if Path::new(path).exists() {
+        let hex = fs::read_to_string(path).map_err(|e| format!("failed to read keyfile {}: {}", path, e))?;
+        let hex = hex.trim().to_string();
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid keyfile {}: expected 64 hex characters", path));
+        }
+        return Ok(hex);
+    }
+    if is_encrypted(db_path) {
+        return Err(format!("keyfile {} is missing but {} is already encrypted; restore the keyfile to open it", path, db_path));
+    }
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    if let Some(parent) = Path::new(path).parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).map_err(|e| format!("failed to create keyfile directory: {}", e))?;
+        }
+    }
+    fs::write(path, &hex).map_err(|e| format!("failed to write keyfile {}: {}", path, e))?;
+    info!("[load_keyfile] generated new database key at {}", path);
+    Ok(hex)
+}
+
+// keyfile 中的原始密钥, 供 PRAGMA key / ATTACH ... KEY 使用; db_path 为该密钥加密的数据库
+pub fn keyfile_key_literal(path: &str, db_path: &str) -> Result<String, String> {
+    let hex = load_keyfile(path, db_path)?;
+    // 原始密钥, SQLCipher 不再做口令派生
+    Ok(quote(&format!("x'{}'", hex)))
+}
+
+fn load_key_literal() -> Result<Option<String>, String> {
+    let storage_config = config::get_config().unwrap().storage;
+    match storage_config.encryption {
+        Encryption::None => Ok(None),
+        Encryption::Keyfile => keyfile_key_literal(&storage_config.keyfile, &super::conn::db_path()).map(Some),
+        Encryption::Passphrase => {
+            let passphrase = std::env::var(&storage_config.passphrase_env)
+                .map_err(|_| format!("environment variable {} is not set", storage_config.passphrase_env))?;
+            if passphrase.is_empty() {
+                return Err(format!("environment variable {} is empty", storage_config.passphrase_env));
+            }
+            Ok(Some(quote(&passphrase)))
+        }
+    }
+}
+
+fn key_literal() -> Result<Option<String>, String> {
+    KEY_LITERAL.clone()
+}
+
+// 每个新连接建立后, 执行任何语句之前调用
+pub fn apply_key(conn: &mut SqliteConnection) -> Result<(), String> {
+    if let Some(key) = key_literal()? {
+        conn.batch_execute(&format!("PRAGMA key = {};", key)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn is_plaintext(path: &str) -> bool {
+    let mut header = [0u8; 16];
+    match fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(_) => &header == SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+// 已有数据且不是明文 sqlite 头, 即已加密
+fn is_encrypted(path: &str) -> bool {
+    fs::metadata(path).map_or(false, |m| m.len() > 0) && !is_plaintext(path)
+}
+
+fn remove_sidecars(db_path: &str) {
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let _ = fs::remove_file(format!("{}{}", db_path, suffix));
+    }
+}
+
+// 启动时调用: 开启加密且数据库仍为明文时, 导出为加密数据库并删除明文文件
+pub fn init(db_path: &str) -> Result<(), String> {
+    let backup_path = format!("{}.plaintext", db_path);
+    // 上次迁移在替换文件之间中断: 恢复明文数据库后重新迁移
+    if !Path::new(db_path).exists() && Path::new(&backup_path).exists() {
+        info!("[encryption::init] restoring {} from interrupted migration", db_path);
+        fs::rename(&backup_path, db_path).map_err(|e| format!("failed to restore plaintext database: {}", e))?;
+    }
+    let key = match key_literal()? {
+        Some(key) => key,
+        None => {
+            if Path::new(db_path).exists() && !is_plaintext(db_path) {
+                return Err(format!("{} is encrypted but storage.encryption is None", db_path));
+            }
+            return Ok(());
+        }
+    };
+    if !Path::new(db_path).exists() || !is_plaintext(db_path) {
+        return Ok(());
+    }
+
+    info!("[encryption::init] migrating plaintext {} to encrypted database", db_path);
+    let encrypted_path = format!("{}.encrypting", db_path);
+    let _ = fs::remove_file(&encrypted_path);
+    remove_sidecars(&encrypted_path);
+    {
+        let mut conn = SqliteConnection::establish(db_path).map_err(|e| e.to_string())?;
+        // 先把 WAL 中的内容写回主文件, 导出才包含全部数据, 替换后也不会留下属于明文库的 -wal/-shm
+        conn.batch_execute("PRAGMA wal_checkpoint(TRUNCATE);").map_err(|e| e.to_string())?;
+        conn.batch_execute(&format!(
+            "ATTACH DATABASE {} AS encrypted KEY {}; SELECT sqlcipher_export('encrypted'); DETACH DATABASE encrypted;",
+            quote(&encrypted_path),
+            key
+        )).map_err(|e| {
+            error!("[encryption::init] sqlcipher_export failed: {}", e);
+            e.to_string()
+        })?;
+    }
+    // 明文库先改名为备份, 加密库就位后再删除备份; 任一步失败时明文库仍可恢复
+    fs::rename(db_path, &backup_path).map_err(|e| format!("failed to move plaintext database aside: {}", e))?;
+    remove_sidecars(db_path);
+    if let Err(e) = fs::rename(&encrypted_path, db_path) {
+        let _ = fs::rename(&backup_path, db_path);
+        return Err(format!("failed to replace database: {}", e));
+    }
+    if let Err(e) = fs::remove_file(&backup_path) {
+        error!("[encryption::init] failed to remove plaintext backup {}: {}", backup_path, e);
+    }
+    info!("[encryption::init] {} is now encrypted", db_path);
+    Ok(())
+}
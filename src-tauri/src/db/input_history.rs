@@ -23,6 +23,7 @@ table! {
         input_height -> Integer,
         input_content -> Text,
         timestamp -> BigInt,
+        pinned -> Bool,
     }
 }
 
@@ -47,35 +48,17 @@ pub struct Input {
     pub input_height: i32,
     pub input_content: String,
     pub timestamp: i64,
+    // 置顶的记录总会作为上下文候选, 且不会过期
+    #[serde(default)]
+    pub pinned: bool,
 }
 
-pub fn insert_history(conn: &mut SqliteConnection, record: &Input) {
+// 只写数据库; 搜索索引由调用方在事务提交后统一更新
+pub fn insert_history(conn: &mut SqliteConnection, record: &Input) -> bool {
     use self::input::dsl::*;
     if let Err(e) = diesel::replace_into(input).values(record).execute(conn) {
         log::error!("Failed to insert history: {}", e);
+        return false;
     }
-
-    // // Add to Tantivy index
-    // if let Some(mut search_index_guard) = get_search_index() {
-    //     if let Some(search_index) = search_index_guard.as_mut() {
-    //         let doc = doc!(
-    //             search_index.input_content_field => record.input_content.clone(),
-    //             search_index.id_field => record.id.clone(),
-    //         );
-    //         if let Err(e) = search_index.add_document(doc) {
-    //             log::error!("Failed to add document to Tantivy index: {}", e);
-    //         }
-    //     }
-    // }
-
-    // 自动清理过期历史
-    let ttl_days = crate::config::get_config().unwrap().system.history_ttl;
-    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
-    let expire = now - (ttl_days as i64) * 24 * 60 * 60 * 1000;
-    use diesel::sql_query;
-    if let Err(e) = sql_query("DELETE FROM input WHERE timestamp < ?")
-        .bind::<diesel::sql_types::BigInt, _>(expire)
-        .execute(conn) {
-        log::error!("Failed to delete expired history: {}", e);
-    }
-} 
\ No newline at end of file
+    true
+}
@@ -0,0 +1,388 @@
+//! input.db 的版本化迁移, 启动时执行一次
+//! 新增表、列或索引时在 MIGRATIONS 末尾追加一条, 不要修改已发布的迁移
+//! 前几条迁移使用 IF NOT EXISTS, 使旧版本按需建表的数据库也能直接接入
+
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Text};
+use diesel::sqlite::SqliteConnection;
+use log::info;
+
+struct Migration {
+    version: i32,
+    name: &'static str,
+    sql: &'static str,
+    // 需要在 Rust 中改写数据时使用, 在 sql 之后、同一事务中执行
+    rewrite: Option<fn(&mut SqliteConnection) -> QueryResult<()>>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_input",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS input (
+                id TEXT PRIMARY KEY,
+                window_id TEXT,
+                window_app TEXT,
+                window_title TEXT,
+                window_class_name TEXT,
+                window_x INTEGER,
+                window_y INTEGER,
+                window_width INTEGER,
+                window_height INTEGER,
+                input_id TEXT,
+                input_title TEXT,
+                input_control_type INTEGER,
+                input_x INTEGER,
+                input_y INTEGER,
+                input_width INTEGER,
+                input_height INTEGER,
+                input_content TEXT,
+                timestamp BIGINT
+            );
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 2,
+        name: "create_ai_token_usage",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS ai_token_usage (
+                apikey TEXT PRIMARY KEY,
+                used_token INTEGER
+            );
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 3,
+        name: "create_privacy_audit",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS privacy_audit (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp BIGINT,
+                provider TEXT,
+                model TEXT,
+                app TEXT,
+                prompt_size INTEGER,
+                prompt_hash TEXT,
+                categories TEXT,
+                value_hashes TEXT
+            );
+            CREATE TRIGGER IF NOT EXISTS privacy_audit_no_update BEFORE UPDATE ON privacy_audit
+            BEGIN
+                SELECT RAISE(ABORT, 'privacy_audit is append-only');
+            END;
+            CREATE TRIGGER IF NOT EXISTS privacy_audit_no_delete BEFORE DELETE ON privacy_audit
+            BEGIN
+                SELECT RAISE(ABORT, 'privacy_audit is append-only');
+            END;
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 4,
+        name: "create_app_consent",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS app_consent (
+                window_app TEXT PRIMARY KEY,
+                decision TEXT,
+                timestamp BIGINT
+            );
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 5,
+        name: "index_input_retrieval",
+        sql: r#"
+            CREATE INDEX IF NOT EXISTS idx_input_field ON input (window_id, input_id, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_input_title_field ON input (window_title, input_title, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_input_app ON input (window_app, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_input_timestamp ON input (timestamp);
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 6,
+        name: "add_input_pinned",
+        sql: r#"
+            ALTER TABLE input ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT 0;
+            CREATE INDEX IF NOT EXISTS idx_input_pinned ON input (pinned, timestamp);
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 7,
+        name: "key_usage_by_profile",
+        sql: r#"
+            CREATE TABLE ai_token_usage_by_profile (
+                profile_id TEXT PRIMARY KEY,
+                used_token INTEGER
+            );
+        "#,
+        rewrite: Some(rewrite_usage_keys),
+    },
+    Migration {
+        version: 8,
+        name: "create_completion_event",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS completion_event (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp BIGINT NOT NULL,
+                app TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                prompt_tokens BIGINT NOT NULL,
+                first_token_ms BIGINT,
+                total_ms BIGINT,
+                shown_chars BIGINT NOT NULL,
+                shown_text TEXT NOT NULL,
+                accepted_chars BIGINT NOT NULL,
+                full_accepts INTEGER NOT NULL,
+                partial_accepts INTEGER NOT NULL,
+                typed_through BOOLEAN NOT NULL,
+                dismissed BOOLEAN NOT NULL,
+                outcome TEXT NOT NULL,
+                error TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_completion_event_timestamp ON completion_event (timestamp);
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 9,
+        name: "index_input_window",
+        sql: r#"
+            CREATE INDEX IF NOT EXISTS idx_input_window ON input (window_id, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_input_window_title ON input (window_title, timestamp);
+        "#,
+        rewrite: None,
+    },
+    Migration {
+        version: 10,
+        name: "completion_event_typed_chars",
+        sql: r#"
+            ALTER TABLE completion_event ADD COLUMN typed_chars BIGINT NOT NULL DEFAULT 0;
+        "#,
+        rewrite: None,
+    },
+];
+
+#[derive(QueryableByName)]
+struct UsageRow {
+    #[diesel(sql_type = Text)]
+    apikey: String,
+    #[diesel(sql_type = BigInt)]
+    used_token: i64,
+}
+
+// 明文 apikey 换成加盐哈希的 profile id, 然后替换旧表
+fn rewrite_usage_keys(conn: &mut SqliteConnection) -> QueryResult<()> {
+    let rows: Vec<UsageRow> = diesel::sql_query("SELECT apikey, COALESCE(used_token, 0) AS used_token FROM ai_token_usage").load(conn)?;
+    for row in rows {
+        diesel::sql_query(
+            "INSERT INTO ai_token_usage_by_profile (profile_id, used_token) VALUES (?, ?) \
+             ON CONFLICT (profile_id) DO UPDATE SET used_token = used_token + excluded.used_token",
+        )
+        .bind::<Text, _>(super::ai_token_usage::api_profile_id(&row.apikey))
+        .bind::<BigInt, _>(row.used_token)
+        .execute(conn)?;
+    }
+    conn.batch_execute("DROP TABLE ai_token_usage; ALTER TABLE ai_token_usage_by_profile RENAME TO ai_token_usage;")
+}
+
+#[derive(QueryableByName)]
+struct VersionRow {
+    #[diesel(sql_type = Integer)]
+    version: i32,
+}
+
+pub fn current_version(conn: &mut SqliteConnection) -> QueryResult<i32> {
+    let rows: Vec<VersionRow> = diesel::sql_query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version").load(conn)?;
+    Ok(rows.first().map_or(0, |r| r.version))
+}
+
+fn latest_version() -> i32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// 执行所有未应用的迁移, 每条迁移和版本记录在同一事务中
+pub fn run(conn: &mut SqliteConnection) -> Result<(), String> {
+    conn.batch_execute(r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at BIGINT NOT NULL
+        );
+    "#).map_err(|e| format!("failed to create schema_version table: {}", e))?;
+
+    let current = current_version(conn).map_err(|e| format!("failed to read schema version: {}", e))?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "input.db schema version {} is newer than this ainput supports ({}), please upgrade ainput",
+            current, latest
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
+        conn.transaction(|conn| {
+            conn.batch_execute(migration.sql)?;
+            if let Some(rewrite) = migration.rewrite {
+                rewrite(conn)?;
+            }
+            diesel::sql_query("INSERT INTO schema_version (version, name, applied_at) VALUES (?, ?, ?)")
+                .bind::<Integer, _>(migration.version)
+                .bind::<Text, _>(migration.name)
+                .bind::<BigInt, _>(now)
+                .execute(conn)?;
+            Ok::<(), diesel::result::Error>(())
+        })
+        .map_err(|e| format!("migration {} ({}) failed: {}", migration.version, migration.name, e))?;
+        info!("[migrations::run] applied migration {} ({})", migration.version, migration.name);
+    }
+    Ok(())
+}
+
+/// 启动时调用一次
+pub fn init() -> Result<(), String> {
+    let mut conn = super::conn::try_establish_connection()?;
+    run(&mut conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 第一个版本的 ainput 按需建出的表, 没有 schema_version
+    const BASELINE_FIXTURE: &str = r#"
+        CREATE TABLE input (
+            id TEXT PRIMARY KEY,
+            window_id TEXT,
+            window_app TEXT,
+            window_title TEXT,
+            window_class_name TEXT,
+            window_x INTEGER,
+            window_y INTEGER,
+            window_width INTEGER,
+            window_height INTEGER,
+            input_id TEXT,
+            input_title TEXT,
+            input_control_type INTEGER,
+            input_x INTEGER,
+            input_y INTEGER,
+            input_width INTEGER,
+            input_height INTEGER,
+            input_content TEXT,
+            timestamp BIGINT
+        );
+        CREATE TABLE ai_token_usage (
+            apikey TEXT PRIMARY KEY,
+            used_token INTEGER
+        );
+        INSERT INTO input (id, window_id, window_app, window_title, input_id, input_title, input_content, timestamp)
+            VALUES ('1', '100', 'notepad.exe', 'note', '7', 'body', 'hello', 1000);
+        INSERT INTO ai_token_usage (apikey, used_token) VALUES ('sk-test', 42);
+    "#;
+
+    #[derive(QueryableByName)]
+    struct CountRow {
+        #[diesel(sql_type = BigInt)]
+        count: i64,
+    }
+
+    #[derive(QueryableByName)]
+    struct AppliedRow {
+        #[diesel(sql_type = Integer)]
+        version: i32,
+        #[diesel(sql_type = BigInt)]
+        applied_at: i64,
+    }
+
+    // 迁移 7 会用到本机哈希盐, 放在临时目录
+    fn memory_conn() -> SqliteConnection {
+        static DATA_DIR: std::sync::Once = std::sync::Once::new();
+        DATA_DIR.call_once(|| {
+            let dir = std::env::temp_dir().join("ainput-migrations-test");
+            let _ = crate::utils::data_dir::init(&dir.to_string_lossy());
+        });
+        SqliteConnection::establish(":memory:").unwrap()
+    }
+
+    fn count(conn: &mut SqliteConnection, sql: &str) -> i64 {
+        diesel::sql_query(sql).load::<CountRow>(conn).unwrap()[0].count
+    }
+
+    fn applied(conn: &mut SqliteConnection) -> Vec<(i32, i64)> {
+        diesel::sql_query("SELECT version, applied_at FROM schema_version ORDER BY version")
+            .load::<AppliedRow>(conn)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.version, r.applied_at))
+            .collect()
+    }
+
+    fn table_exists(conn: &mut SqliteConnection, name: &str) -> bool {
+        count(conn, &format!("SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = '{}'", name)) == 1
+    }
+
+    #[test]
+    fn versions_are_increasing() {
+        assert!(MIGRATIONS.windows(2).all(|w| w[0].version < w[1].version));
+        assert_eq!(MIGRATIONS[0].version, 1);
+    }
+
+    #[test]
+    fn fresh_database_reaches_latest_version() {
+        let mut conn = memory_conn();
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&mut conn).unwrap(), latest_version());
+        assert_eq!(applied(&mut conn).len(), MIGRATIONS.len());
+        for table in ["input", "ai_token_usage", "privacy_audit", "app_consent", "completion_event"] {
+            assert!(table_exists(&mut conn, table), "missing table {}", table);
+        }
+    }
+
+    #[test]
+    fn baseline_fixture_is_upgraded_in_place() {
+        let mut conn = memory_conn();
+        conn.batch_execute(BASELINE_FIXTURE).unwrap();
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&mut conn).unwrap(), latest_version());
+
+        assert_eq!(count(&mut conn, "SELECT COUNT(*) AS count FROM input WHERE id = '1' AND pinned = 0"), 1);
+        assert_eq!(count(&mut conn, "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'index' AND name LIKE 'idx_input_%'"), 7);
+
+        // 用量按 profile id 保存, 不再保留明文 apikey
+        let profile = crate::db::ai_token_usage::api_profile_id("sk-test");
+        let usage = crate::db::ai_token_usage::get_used_token(&mut conn, &profile);
+        assert_eq!(usage, 42);
+        assert_eq!(count(&mut conn, "SELECT COUNT(*) AS count FROM ai_token_usage"), 1);
+        assert!(!table_exists(&mut conn, "ai_token_usage_by_profile"));
+    }
+
+    #[test]
+    fn rerun_is_a_no_op() {
+        let mut conn = memory_conn();
+        conn.batch_execute(BASELINE_FIXTURE).unwrap();
+        run(&mut conn).unwrap();
+        let before = applied(&mut conn);
+        run(&mut conn).unwrap();
+        assert_eq!(applied(&mut conn), before);
+        assert_eq!(count(&mut conn, "SELECT COUNT(*) AS count FROM input"), 1);
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let mut conn = memory_conn();
+        run(&mut conn).unwrap();
+        diesel::sql_query("INSERT INTO schema_version (version, name, applied_at) VALUES (?, 'future', 0)")
+            .bind::<Integer, _>(latest_version() + 1)
+            .execute(&mut conn)
+            .unwrap();
+        assert!(run(&mut conn).is_err());
+    }
+}
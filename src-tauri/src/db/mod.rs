@@ -1,3 +1,14 @@
 pub mod conn;
+pub mod migrations;
+#[cfg(test)]
+mod bench;
+pub mod writer;
+pub mod archive;
 pub mod input_history;
-pub mod ai_token_usage; 
\ No newline at end of file
+pub mod ai_token_usage;
+pub mod privacy_audit;
+pub mod completion_event;
+pub mod stats;
+pub mod encryption;
+pub mod app_consent;
+pub mod vault;
@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use diesel::prelude::*;
+use indexmap::IndexMap;
+use log::{error, info};
+use serde::Serialize;
+
+use crate::ai::privacy::Detection;
+use crate::utils::hash::salted_hash;
+
+// privacy_audit 表结构和 schema, 只追加不修改: 每条外发请求一行, 只保存哈希和计数, 不保存原文
+
+table! {
+    privacy_audit (id) {
+        id -> BigInt,
+        timestamp -> BigInt,
+        provider -> Text,
+        model -> Text,
+        app -> Text,
+        prompt_size -> BigInt,
+        prompt_hash -> Text,
+        categories -> Text,
+        value_hashes -> Text,
+    }
+}
+
+#[derive(Queryable, Serialize, Debug, Clone)]
+#[diesel(table_name = privacy_audit)]
+pub struct PrivacyAudit {
+    pub id: i64,
+    pub timestamp: i64,
+    pub provider: String,
+    pub model: String,
+    pub app: String,
+    pub prompt_size: i64,
+    pub prompt_hash: String,
+    // json: {"类别": 命中次数}
+    pub categories: String,
+    // json: ["原文加盐哈希"]
+    pub value_hashes: String,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = privacy_audit)]
+pub struct NewPrivacyAudit {
+    pub timestamp: i64,
+    pub provider: String,
+    pub model: String,
+    pub app: String,
+    pub prompt_size: i64,
+    pub prompt_hash: String,
+    pub categories: String,
+    pub value_hashes: String,
+}
+
+impl NewPrivacyAudit {
+    pub fn new(provider: &str, model: &str, app: &str, prompt: &str, detections: &[Detection]) -> Self {
+        let mut categories: IndexMap<String, usize> = IndexMap::new();
+        for detection in detections {
+            *categories.entry(detection.category.clone()).or_insert(0) += 1;
+        }
+        let value_hashes: Vec<&str> = detections.iter().map(|d| d.value_hash.as_str()).collect();
+        Self {
+            timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64,
+            provider: provider.to_string(),
+            model: model.to_string(),
+            app: app.to_string(),
+            prompt_size: prompt.chars().count() as i64,
+            prompt_hash: salted_hash(prompt),
+            categories: serde_json::to_string(&categories).unwrap_or_default(),
+            value_hashes: serde_json::to_string(&value_hashes).unwrap_or_default(),
+        }
+    }
+}
+
+pub fn insert_audit(conn: &mut SqliteConnection, record: &NewPrivacyAudit) {
+    if let Err(e) = diesel::insert_into(privacy_audit::table).values(record).execute(conn) {
+        error!("Failed to insert privacy audit: {}", e);
+    }
+}
+
+pub fn query_audit(
+    conn: &mut SqliteConnection,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<i64>,
+) -> QueryResult<Vec<PrivacyAudit>> {
+    use self::privacy_audit::dsl::*;
+    let mut query = privacy_audit.into_boxed();
+    if let Some(from) = from {
+        query = query.filter(timestamp.ge(from));
+    }
+    if let Some(to) = to {
+        query = query.filter(timestamp.lt(to));
+    }
+    if let Some(limit) = limit {
+        query = query.limit(limit);
+    }
+    query.order(timestamp.desc()).load::<PrivacyAudit>(conn)
+}
+
+#[tauri::command]
+pub async fn get_privacy_audit_command(from: Option<i64>, to: Option<i64>, limit: Option<i64>) -> Result<Vec<PrivacyAudit>, String> {
+    crate::db::conn::with_conn(move |conn| query_audit(conn, from, to, limit))
+        .await?
+        .map_err(|e| e.to_string())
+}
+
+// 导出为 jsonl, 每行一条审计记录, 返回导出条数
+#[tauri::command]
+pub async fn export_privacy_audit_command(path: String, from: Option<i64>, to: Option<i64>) -> Result<usize, String> {
+    let records = crate::db::conn::with_conn(move |conn| query_audit(conn, from, to, None))
+        .await?
+        .map_err(|e| e.to_string())?;
+    let file = File::create(&path).map_err(|e| e.to_string())?;
+    let mut writer = BufWriter::new(file);
+    for record in records.iter().rev() {
+        let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", line).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    info!("[export_privacy_audit_command] exported {} records to {}", records.len(), path);
+    Ok(records.len())
+}
@@ -0,0 +1,296 @@
+//! 本地使用统计: 按应用、提供方和日期汇总手动输入与采纳的字数、采纳率、首字耗时中位数和用量
+//! 数据来自 completion_event, 只在本地计算; 可按 stats.summary_interval 定期发送摘要通知
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use diesel::prelude::*;
+use log::{error, info};
+use serde::Serialize;
+use tauri_plugin_notification::NotificationExt;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::config;
+use crate::utils::data_dir;
+use crate::utils::locale::{self, UiLang};
+use crate::APP_HANDLE;
+
+use super::ai_token_usage;
+use super::completion_event::completion_event;
+use super::conn::{establish_connection, with_conn};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+const HOUR_MILLIS: i64 = 60 * 60 * 1000;
+// 未指定时间范围时统计最近 30 天
+const DEFAULT_RANGE_DAYS: i64 = 30;
+const SUMMARY_STAMP: &str = "last_stats_summary";
+const SUMMARY_CHECK_INTERVAL: Duration = Duration::from_secs(600);
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct StatsRow {
+    // 应用名、提供方或日期(YYYY-MM-DD), 汇总行为空
+    pub key: String,
+    pub requests: i64,
+    // 有候选展示的请求数
+    pub shown: i64,
+    // 有整段或部分采纳的请求数
+    pub accepted: i64,
+    // 手动输入的字数: 各次请求期间输入框新增的字数减去采纳的字数
+    pub typed_chars: i64,
+    pub accepted_chars: i64,
+    // accepted / shown
+    pub acceptance_rate: f64,
+    pub median_first_token_ms: Option<i64>,
+    // prompt 与展示候选的字数之和, 与 ai_token_usage 的计数方式一致
+    pub tokens: i64,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Stats {
+    pub from: i64,
+    pub to: i64,
+    pub total: StatsRow,
+    pub by_app: Vec<StatsRow>,
+    pub by_provider: Vec<StatsRow>,
+    pub by_day: Vec<StatsRow>,
+    // 当前提供方配置的累计用量
+    pub used_token: i64,
+}
+
+#[derive(Default)]
+struct Acc {
+    requests: i64,
+    shown: i64,
+    accepted: i64,
+    typed_chars: i64,
+    accepted_chars: i64,
+    first_token_ms: Vec<i64>,
+    tokens: i64,
+}
+
+type EventRow = (String, String, i64, Option<i64>, i64, i64, i64, i32, i32, i64);
+
+impl Acc {
+    fn add_event(&mut self, (_, _, _, first_token_ms, shown_chars, accepted_chars, typed_chars, full_accepts, partial_accepts, prompt_tokens): &EventRow) {
+        self.requests += 1;
+        if *shown_chars > 0 {
+            self.shown += 1;
+        }
+        if full_accepts + partial_accepts > 0 {
+            self.accepted += 1;
+        }
+        self.accepted_chars += accepted_chars;
+        self.typed_chars += typed_chars;
+        if let Some(ms) = first_token_ms {
+            self.first_token_ms.push(*ms);
+        }
+        self.tokens += prompt_tokens + shown_chars;
+    }
+
+    fn into_row(mut self, key: String) -> StatsRow {
+        self.first_token_ms.sort_unstable();
+        let n = self.first_token_ms.len();
+        let median_first_token_ms = match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(self.first_token_ms[n / 2]),
+            _ => Some((self.first_token_ms[n / 2 - 1] + self.first_token_ms[n / 2]) / 2),
+        };
+        StatsRow {
+            key,
+            requests: self.requests,
+            shown: self.shown,
+            accepted: self.accepted,
+            typed_chars: self.typed_chars,
+            accepted_chars: self.accepted_chars,
+            acceptance_rate: if self.shown > 0 { self.accepted as f64 / self.shown as f64 } else { 0.0 },
+            median_first_token_ms,
+            tokens: self.tokens,
+        }
+    }
+}
+
+fn now_millis() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64
+}
+
+// 按本地时区分日, 偏移取当前值
+fn local_offset_millis() -> i64 {
+    UtcOffset::current_local_offset().map_or(0, |o| o.whole_seconds() as i64 * 1000)
+}
+
+fn day_key(day: i64) -> String {
+    let date = OffsetDateTime::from_unix_timestamp(day * DAY_MILLIS / 1000).unwrap_or(OffsetDateTime::UNIX_EPOCH).date();
+    format!("{:04}-{:02}-{:02}", date.year(), date.month() as u8, date.day())
+}
+
+fn into_rows(groups: BTreeMap<String, Acc>) -> Vec<StatsRow> {
+    let mut rows: Vec<StatsRow> = groups.into_iter().map(|(key, acc)| acc.into_row(key)).collect();
+    rows.sort_by(|a, b| b.requests.cmp(&a.requests));
+    rows
+}
+
+pub fn get_stats(conn: &mut SqliteConnection, from: i64, to: i64) -> QueryResult<Stats> {
+    let offset = local_offset_millis();
+    let events = completion_event::table
+        .filter(completion_event::timestamp.ge(from))
+        .filter(completion_event::timestamp.lt(to))
+        .select((
+            completion_event::app,
+            completion_event::provider,
+            completion_event::timestamp,
+            completion_event::first_token_ms,
+            completion_event::shown_chars,
+            completion_event::accepted_chars,
+            completion_event::typed_chars,
+            completion_event::full_accepts,
+            completion_event::partial_accepts,
+            completion_event::prompt_tokens,
+        ))
+        .load::<EventRow>(conn)?;
+
+    let mut total = Acc::default();
+    let mut by_app: BTreeMap<String, Acc> = BTreeMap::new();
+    let mut by_provider: BTreeMap<String, Acc> = BTreeMap::new();
+    let mut by_day: BTreeMap<String, Acc> = BTreeMap::new();
+    for event in &events {
+        let (app, provider, timestamp, ..) = event;
+        total.add_event(event);
+        by_app.entry(app.clone()).or_default().add_event(event);
+        by_provider.entry(provider.clone()).or_default().add_event(event);
+        by_day.entry(day_key((timestamp + offset).div_euclid(DAY_MILLIS))).or_default().add_event(event);
+    }
+
+    let mut by_day: Vec<StatsRow> = by_day.into_iter().map(|(key, acc)| acc.into_row(key)).collect();
+    by_day.reverse();
+    Ok(Stats {
+        from,
+        to,
+        total: total.into_row(String::new()),
+        by_app: into_rows(by_app),
+        by_provider: into_rows(by_provider),
+        by_day,
+        used_token: ai_token_usage::get_used_token(conn, &ai_token_usage::current_profile_id()),
+    })
+}
+
+// from/to 为毫秒时间戳, 左闭右开
+#[tauri::command]
+pub async fn get_stats_command(from: Option<i64>, to: Option<i64>) -> Result<Stats, String> {
+    let to = to.unwrap_or_else(now_millis);
+    let from = from.unwrap_or(to - DEFAULT_RANGE_DAYS * DAY_MILLIS);
+    with_conn(move |conn| get_stats(conn, from, to)).await?.map_err(|e| e.to_string())
+}
+
+fn summary_title(lang: UiLang) -> &'static str {
+    match lang {
+        UiLang::Zh => "ainput 使用摘要",
+        UiLang::En => "ainput summary",
+    }
+}
+
+fn summary_body(stats: &Stats, hours: u64, lang: UiLang) -> String {
+    let total = &stats.total;
+    let first_token = total.median_first_token_ms.map_or("-".to_string(), |ms| format!("{}ms", ms));
+    let top_app = stats.by_app.first().map(|row| row.key.as_str());
+    match lang {
+        UiLang::Zh => {
+            let period = if hours % 24 == 0 { format!("{} 天", hours / 24) } else { format!("{} 小时", hours) };
+            format!(
+                "最近 {}: 采纳 {} 字, 手动输入 {} 字, {} 次候选采纳率 {:.0}%, 首字耗时中位数 {}, 用量 {} tokens{}",
+                period,
+                total.accepted_chars,
+                total.typed_chars,
+                total.shown,
+                total.acceptance_rate * 100.0,
+                first_token,
+                total.tokens,
+                top_app.map_or(String::new(), |app| format!(", 最常用 {}", app)),
+            )
+        }
+        UiLang::En => {
+            let period = if hours % 24 == 0 { format!("{} day(s)", hours / 24) } else { format!("{} hour(s)", hours) };
+            format!(
+                "Last {}: accepted {} chars, typed {} chars, acceptance {:.0}% of {} suggestions, median first token {}, {} tokens{}",
+                period,
+                total.accepted_chars,
+                total.typed_chars,
+                total.acceptance_rate * 100.0,
+                total.shown,
+                first_token,
+                total.tokens,
+                top_app.map_or(String::new(), |app| format!(", top app {}", app)),
+            )
+        }
+    }
+}
+
+// 上次摘要的时间保存在数据目录中; 首次开启时从现在开始计时
+fn summary_due(interval_hours: u64) -> bool {
+    match std::fs::metadata(data_dir::path(SUMMARY_STAMP)).and_then(|m| m.modified()) {
+        Ok(last) => last.elapsed().map_or(false, |elapsed| elapsed >= Duration::from_secs(interval_hours * 3600)),
+        Err(_) => {
+            let _ = std::fs::write(data_dir::path(SUMMARY_STAMP), b"");
+            false
+        }
+    }
+}
+
+fn send_summary(hours: u64) {
+    let to = now_millis();
+    let from = to - hours as i64 * HOUR_MILLIS;
+    let stats = match get_stats(&mut establish_connection(), from, to) {
+        Ok(stats) => stats,
+        Err(e) => {
+            error!("[stats::send_summary] failed to compute stats: {}", e);
+            return;
+        }
+    };
+    let lang = locale::ui_lang();
+    let body = summary_body(&stats, hours, lang);
+    info!("[stats::send_summary] {}", body);
+    if let Some(app_handle) = APP_HANDLE.lock().unwrap().as_ref() {
+        let _ = app_handle.notification().builder().title(summary_title(lang)).body(body).show();
+    }
+    if let Err(e) = std::fs::write(data_dir::path(SUMMARY_STAMP), b"") {
+        error!("[stats::send_summary] failed to write stamp: {}", e);
+    }
+}
+
+pub fn start_summary_scheduler() {
+    std::thread::spawn(|| loop {
+        let hours = config::get_config().unwrap().stats.summary_interval;
+        if hours > 0 && summary_due(hours) {
+            send_summary(hours);
+        }
+        std::thread::sleep(SUMMARY_CHECK_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_chars_come_from_events() {
+        let mut acc = Acc::default();
+        acc.add_event(&("app.exe".into(), "openai".into(), 0, Some(300), 20, 12, 30, 1, 0, 100));
+        acc.add_event(&("app.exe".into(), "openai".into(), 0, None, 0, 0, 5, 0, 0, 80));
+        let row = acc.into_row("openai".into());
+        assert_eq!(row.typed_chars, 35);
+        assert_eq!(row.accepted_chars, 12);
+        assert_eq!(row.shown, 1);
+        assert_eq!(row.accepted, 1);
+        assert_eq!(row.median_first_token_ms, Some(300));
+    }
+
+    #[test]
+    fn summary_follows_ui_language() {
+        let stats = Stats { total: StatsRow { typed_chars: 35, accepted_chars: 12, ..Default::default() }, ..Default::default() };
+        let zh = summary_body(&stats, 168, UiLang::Zh);
+        assert!(zh.starts_with("最近 7 天"), "{}", zh);
+        assert!(zh.contains("手动输入 35 字"), "{}", zh);
+        let en = summary_body(&stats, 12, UiLang::En);
+        assert!(en.starts_with("Last 12 hour(s)"), "{}", en);
+        assert!(en.contains("typed 35 chars"), "{}", en);
+    }
+}
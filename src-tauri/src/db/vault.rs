@@ -0,0 +1,123 @@
+//! 敏感值保险库: 入库前脱敏时, 需要之后还原的值保存在这里, 而不是 input.db 中
+//! 保险库是单独的 SQLCipher 数据库, 始终使用单独的 keyfile 加密, 与 input.db 的加密设置无关
+//! 默认 keyfile 与 vault.db 同在数据目录, 只防止单独泄露 vault.db; 需要防止整个数据目录被复制时, 将 privacy.vault_keyfile 指向其它位置
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use diesel::{connection::SimpleConnection, prelude::*};
+use log::error;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::config;
+use crate::utils::hash::salted_hash;
+
+pub const VAULT_FILE: &str = "vault.db";
+
+static PLACEHOLDER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[VAULT_[0-9a-f]{12}\]").unwrap());
+// 首次使用时打开并建表, 之后复用; 打开失败时不缓存, 下次重试
+static VAULT_CONN: Lazy<Mutex<Option<SqliteConnection>>> = Lazy::new(|| Mutex::new(None));
+
+table! {
+    vault (placeholder) {
+        placeholder -> Text,
+        category -> Text,
+        value -> Text,
+        timestamp -> BigInt,
+    }
+}
+
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = vault)]
+struct VaultEntry {
+    placeholder: String,
+    category: String,
+    value: String,
+    timestamp: i64,
+}
+
+// 供前端展示, 不包含原值
+#[derive(Serialize, Debug, Clone)]
+pub struct VaultItem {
+    pub placeholder: String,
+    pub category: String,
+    pub timestamp: i64,
+}
+
+fn establish_vault_connection() -> Result<SqliteConnection, String> {
+    let keyfile = config::get_config().unwrap().privacy.vault_keyfile;
+    let vault_path = crate::utils::data_dir::path_str(VAULT_FILE);
+    let key = super::encryption::keyfile_key_literal(&keyfile, &vault_path)?;
+    let mut conn = SqliteConnection::establish(&vault_path).map_err(|e| e.to_string())?;
+    conn.batch_execute(&format!("PRAGMA key = {};", key)).map_err(|e| e.to_string())?;
+    conn.batch_execute(r#"
+        CREATE TABLE IF NOT EXISTS vault (
+            placeholder TEXT PRIMARY KEY,
+            category TEXT,
+            value TEXT,
+            timestamp BIGINT
+        )
+    "#).map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+fn with_vault<T, F: FnOnce(&mut SqliteConnection) -> QueryResult<T>>(f: F) -> Result<T, String> {
+    let mut guard = VAULT_CONN.lock().unwrap();
+    if guard.is_none() {
+        *guard = Some(establish_vault_connection()?);
+    }
+    f(guard.as_mut().unwrap()).map_err(|e| e.to_string())
+}
+
+// 保存敏感值, 返回稳定的占位符(同一取值总是得到同一占位符); 失败时返回 None
+pub fn store(category: &str, value: &str) -> Option<String> {
+    let placeholder = format!("[VAULT_{}]", &salted_hash(value)[..12]);
+    let entry = VaultEntry {
+        placeholder: placeholder.clone(),
+        category: category.to_string(),
+        value: value.to_string(),
+        timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64,
+    };
+    let result = with_vault(|conn| diesel::insert_or_ignore_into(vault::table).values(&entry).execute(conn));
+    match result {
+        Ok(_) => Some(placeholder),
+        Err(e) => {
+            error!("[vault::store] failed to store value: {}", e);
+            None
+        }
+    }
+}
+
+// 文本中出现的保险库占位符 -> 原值, 用于模型输出的还原
+pub fn resolve_placeholders(text: &str) -> HashMap<String, String> {
+    let placeholders: Vec<String> = PLACEHOLDER_RE.find_iter(text).map(|m| m.as_str().to_string()).collect();
+    if placeholders.is_empty() {
+        return HashMap::new();
+    }
+    let result = with_vault(|conn| vault::table.filter(vault::placeholder.eq_any(placeholders)).load::<VaultEntry>(conn));
+    match result {
+        Ok(entries) => entries.into_iter().map(|e| (e.placeholder, e.value)).collect(),
+        Err(e) => {
+            error!("[vault::resolve_placeholders] failed to read vault: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_vault_command() -> Result<Vec<VaultItem>, String> {
+    let entries = with_vault(|conn| vault::table.order(vault::timestamp.desc()).load::<VaultEntry>(conn))?;
+    Ok(entries
+        .into_iter()
+        .map(|e| VaultItem { placeholder: e.placeholder, category: e.category, timestamp: e.timestamp })
+        .collect())
+}
+
+// 删除后历史中的占位符将无法再还原
+#[tauri::command]
+pub fn delete_vault_entry_command(placeholder: String) -> Result<(), String> {
+    with_vault(|conn| diesel::delete(vault::table.filter(vault::placeholder.eq(placeholder))).execute(conn))?;
+    Ok(())
+}
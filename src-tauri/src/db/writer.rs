@@ -0,0 +1,139 @@
+//! 单独的写线程: 历史、用量、审计和补全事件写入都通过通道提交, 调用方(焦点循环、流式输出回调)不等待磁盘
+//! 一小段时间内的写入合并为一批并在同一事务中提交, 用量增量按 profile id 累加后一次更新
+//! 退出前调用 flush_pending, 等写线程写完已提交的内容
+
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use diesel::Connection;
+use log::{error, info};
+use once_cell::sync::OnceCell;
+
+use crate::context::history::history::{self, InputHistory};
+use crate::context::history::search;
+use super::ai_token_usage;
+use super::conn::establish_connection;
+use super::completion_event::{self, NewCompletionEvent};
+use super::privacy_audit::{self, NewPrivacyAudit};
+
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+// 退出时最多等待写线程的时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub enum WriteOp {
+    History(Box<InputHistory>),
+    Usage { profile_id: String, delta: i64 },
+    Audit(NewPrivacyAudit),
+    Completion(Box<NewCompletionEvent>),
+    // 立即写入当前批次, 完成后回复
+    Flush(Sender<()>),
+}
+
+static SENDER: OnceCell<Mutex<Sender<WriteOp>>> = OnceCell::new();
+
+pub fn start() {
+    let (tx, rx) = channel::<WriteOp>();
+    if SENDER.set(Mutex::new(tx)).is_err() {
+        return;
+    }
+    std::thread::spawn(move || {
+        info!("[writer] started");
+        while let Ok(op) = rx.recv() {
+            let mut batch = Vec::new();
+            let mut acks = Vec::new();
+            collect(op, &mut batch, &mut acks);
+            let deadline = Instant::now() + FLUSH_INTERVAL;
+            while acks.is_empty() {
+                match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(op) => collect(op, &mut batch, &mut acks),
+                    Err(_) => break,
+                }
+            }
+            if !batch.is_empty() {
+                flush(batch);
+            }
+            for ack in acks {
+                let _ = ack.send(());
+            }
+        }
+        info!("[writer] stopped");
+    });
+}
+
+fn collect(op: WriteOp, batch: &mut Vec<WriteOp>, acks: &mut Vec<Sender<()>>) {
+    match op {
+        WriteOp::Flush(ack) => acks.push(ack),
+        op => batch.push(op),
+    }
+}
+
+// 整批在一个事务中提交; 单条写入失败只记录日志, 不影响同批其它写入
+// 事务提交成功后再把本批历史一次性加入搜索索引, 回滚的批次不会在索引中留下记录
+fn flush(batch: Vec<WriteOp>) {
+    let mut conn = establish_connection();
+    let mut saved = Vec::new();
+    let result = conn.transaction(|conn| {
+        let mut usage: HashMap<String, i64> = HashMap::new();
+        for op in batch {
+            match op {
+                WriteOp::History(h) => saved.extend(history::save_history(conn, &h)),
+                WriteOp::Usage { profile_id, delta } => *usage.entry(profile_id).or_insert(0) += delta,
+                WriteOp::Audit(record) => privacy_audit::insert_audit(conn, &record),
+                WriteOp::Completion(record) => completion_event::insert_event(conn, &record),
+                WriteOp::Flush(_) => {}
+            }
+        }
+        for (profile_id, delta) in &usage {
+            if let Err(e) = ai_token_usage::increment_used_token(conn, profile_id, *delta) {
+                error!("[writer] failed to update token usage: {}", e);
+            }
+        }
+        Ok::<(), diesel::result::Error>(())
+    });
+    match result {
+        Ok(()) => search::index_records(&saved),
+        Err(e) => error!("[writer] failed to commit batch: {}", e),
+    }
+}
+
+fn send(op: WriteOp) {
+    match SENDER.get() {
+        Some(sender) => {
+            if sender.lock().unwrap().send(op).is_err() {
+                error!("[writer] writer thread is gone, dropping write");
+            }
+        }
+        None => error!("[writer] writer thread not started, dropping write"),
+    }
+}
+
+pub fn save_history(input_history: InputHistory) {
+    send(WriteOp::History(Box::new(input_history)));
+}
+
+pub fn increment_used_token(profile_id: &str, delta: i64) {
+    send(WriteOp::Usage { profile_id: profile_id.to_string(), delta });
+}
+
+pub fn insert_audit(record: NewPrivacyAudit) {
+    send(WriteOp::Audit(record));
+}
+
+pub fn insert_completion_event(record: NewCompletionEvent) {
+    send(WriteOp::Completion(Box::new(record)));
+}
+
+/// 等写线程写完此前提交的内容, 退出进程前调用
+pub fn flush_pending() {
+    let Some(sender) = SENDER.get() else { return };
+    let (tx, rx) = channel();
+    if sender.lock().unwrap().send(WriteOp::Flush(tx)).is_err() {
+        return;
+    }
+    match rx.recv_timeout(SHUTDOWN_TIMEOUT) {
+        Ok(()) => info!("[writer] pending writes flushed"),
+        Err(_) => error!("[writer] timed out waiting for pending writes"),
+    }
+}
@@ -4,6 +4,7 @@ use log::debug;
 use once_cell::sync::Lazy;
 
 use crate::config;
+use crate::utils::logger::redact;
 
 struct KeyboardState {
     hold_keys: HashSet<String>,
@@ -23,7 +24,7 @@ pub fn handle_keyboard_event(app_handle: &tauri::AppHandle, key: &str, is_press:
     if !super::get_input_state() {
         return false;
     }
-    debug!("[handle_keyboard_event] key: {}, is_down: {}", key, is_press);
+    debug!("[handle_keyboard_event] key: {}, is_down: {}", redact(key), is_press);
     let config = config::get_config().unwrap();
     let mut state = KEYBOARD_STATE.lock().unwrap();
     if is_press {
@@ -42,7 +43,7 @@ pub fn handle_keyboard_event(app_handle: &tauri::AppHandle, key: &str, is_press:
         if keys.contains(&key.to_string()) {
             match Some(cmd.as_str()) {
                 Some(config::keybinding::EXIT_OVERLAY) => {
-                    super::end_overlay();
+                    super::dismiss_overlay();
                     return true;
                 },
                 Some(config::keybinding::ACCEPT_CANDIDATE) => {
@@ -4,7 +4,7 @@ pub mod keyboard;
 use std::sync::Mutex;
 use log::{debug, error, info};
 use once_cell::sync::Lazy;
-use crate::{ai::ai_client, config, context, os::element, overlay::{self, overlay::resize_overlay_window}};
+use crate::{ai::ai_client, config, context, db::app_consent::{self, ConsentDecision}, os::element, overlay::{self, overlay::resize_overlay_window}, utils::logger::redact};
 use tauri::{LogicalPosition, Manager};
 use tauri_plugin_notification::NotificationExt;
 use crate::APP_HANDLE;
@@ -13,14 +13,53 @@ use std::thread;
 use tauri::async_runtime::JoinHandle;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering, AtomicU64};
+use std::time::Instant;
+use crate::ai::privacy::redact_for_storage;
+use crate::db::completion_event::NewCompletionEvent;
 
 static INPUT_STATE: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
 static FORMER_FOCUSED_INPUT: Lazy<RwLock<Option<element::FocusedInput>>> = Lazy::new(|| RwLock::new(None));
+// 焦点丢失前最后一个输入框, 用于托盘等场景下的请求预览
+static LAST_FOCUSED_INPUT: Lazy<RwLock<Option<element::FocusedInput>>> = Lazy::new(|| RwLock::new(None));
 static SELECTED_CANDIDATE: Lazy<RwLock<String>> = Lazy::new(|| RwLock::new(String::new()));
 static CANDIDATE: Lazy<RwLock<String>> = Lazy::new(|| RwLock::new(String::new()));
 static OVERLAY_TASK_HANDLE: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
 static OVERLAY_CANCEL_TOKEN: Lazy<Mutex<Option<Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(None));
 static TASK_GENERATION: Lazy<AtomicU64> = Lazy::new(|| AtomicU64::new(0));
+// 最近被清空(内容已发送)的输入框 (窗口句柄, 输入框 id), 该输入框下一条历史作为新消息保存, 不覆盖上一条
+static CLEARED_FIELD: Lazy<Mutex<Option<(i64, String)>>> = Lazy::new(|| Mutex::new(None));
+// 当前补全请求的展示和采纳情况, 请求被替换或候选框关闭时写入 completion_event
+static COMPLETION: Lazy<Mutex<Option<CompletionTracker>>> = Lazy::new(|| Mutex::new(None));
+
+struct CompletionTracker {
+    generation: u64,
+    started: Instant,
+    shown: String,
+    // 请求开始时和最近一次观察到的输入框字数, 差值减去采纳的字数即手动输入的字数
+    base_chars: i64,
+    content_chars: i64,
+    event: NewCompletionEvent,
+}
+
+// generation 为 None 时更新当前请求, 否则只在仍是该次请求时更新
+fn track_completion<F: FnOnce(&mut CompletionTracker)>(generation: Option<u64>, f: F) {
+    if let Some(tracker) = COMPLETION.lock().unwrap().as_mut() {
+        if generation.map_or(true, |g| g == tracker.generation) {
+            f(tracker);
+        }
+    }
+}
+
+fn finish_completion() {
+    let Some(tracker) = COMPLETION.lock().unwrap().take() else { return };
+    let mut event = tracker.event;
+    event.shown_chars = tracker.shown.chars().count() as i64;
+    event.shown_text = redact_for_storage(&tracker.shown);
+    event.typed_chars = (tracker.content_chars - tracker.base_chars - event.accepted_chars).max(0);
+    event.outcome = event.classify().to_string();
+    debug!("[finish_completion] outcome: {}", event.outcome);
+    crate::db::writer::insert_completion_event(event);
+}
 
 pub fn set_input_state(state: bool) {
     debug!("[set_input_state] set to {}", state);
@@ -33,8 +72,26 @@ pub fn get_input_state() -> bool {
     *input_state
 }
 
+// 当前聚焦的输入框, 没有时返回最近一次聚焦的输入框
+pub fn get_last_focused_input() -> Option<element::FocusedInput> {
+    if let Some(focused_input) = FORMER_FOCUSED_INPUT.read().unwrap().clone() {
+        return Some(focused_input);
+    }
+    LAST_FOCUSED_INPUT.read().unwrap().clone()
+}
+
 fn start_overlay(focused_input: element::FocusedInput) {
-    info!("[start_overlay] for app: {}, input: {}", focused_input.window_element.app, focused_input.input_element.text);
+    info!("[start_overlay] for app: {}, input: {}", focused_input.window_element.app, redact(&focused_input.input_element.text));
+    finish_completion();
+    // 在构建 Context 之前检查应用授权
+    let consent = app_consent::get_consent(&focused_input.window_element.app);
+    if consent == Some(ConsentDecision::Deny) {
+        info!("[start_overlay] completions denied for app: {}", focused_input.window_element.app);
+        end_overlay();
+        return;
+    }
+    let ask_consent = consent.is_none() && config::get_config().unwrap().privacy.require_consent;
+    let local_only = consent == Some(ConsentDecision::LocalOnly);
     set_input_state(true);
     end_overlay_stream_task();
 
@@ -60,7 +117,27 @@ fn start_overlay(focused_input: element::FocusedInput) {
             overlay::overlay::top_window(&window);
         }
     }
-    
+
+    if ask_consent {
+        info!("[start_overlay] asking consent for app: {}", focused_input.window_element.app);
+        overlay::overlay::request_consent(&focused_input.window_element.app);
+        return;
+    }
+
+    let content_chars = focused_input.input_element.content.chars().count() as i64;
+    *COMPLETION.lock().unwrap() = Some(CompletionTracker {
+        generation: my_generation,
+        started: Instant::now(),
+        shown: String::new(),
+        base_chars: content_chars,
+        content_chars,
+        event: NewCompletionEvent {
+            timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64,
+            app: focused_input.window_element.app.clone(),
+            ..Default::default()
+        },
+    });
+
     let handle = tauri::async_runtime::spawn(async move {
         let current_generation = TASK_GENERATION.load(Ordering::Relaxed);
         if my_generation < current_generation {
@@ -68,16 +145,12 @@ fn start_overlay(focused_input: element::FocusedInput) {
             return;
         }
 
-        let context = match tokio::task::spawn_blocking(move || context::Context::new(&focused_input)).await {
-            Ok(Some(ctx)) => ctx,
-            Ok(None) => {
+        let context = match context::Context::new(&focused_input).await {
+            Some(ctx) => ctx,
+            None => {
                 error!("[start_overlay] Context::new returned None.");
                 return;
             }
-            Err(_) => {
-                error!("[start_overlay] Failed to create context in background task (JoinError).");
-                return;
-            }
         };
 
         let current_generation = TASK_GENERATION.load(Ordering::Relaxed);
@@ -86,10 +159,22 @@ fn start_overlay(focused_input: element::FocusedInput) {
             return;
         }
 
-        let client = ai_client::AiClient::new();
+        let client = ai_client::AiClient::new().with_local_only(local_only);
+        let request_info = client.request_info(&context);
+        track_completion(Some(my_generation), |t| {
+            t.event.provider = request_info.provider.to_string();
+            t.event.model = request_info.model;
+            t.event.prompt_tokens = request_info.prompt_tokens;
+        });
         let cancel_token_clone = cancel_token.clone();
-        let result = client.stream_request_ai(context, |c| {
-                debug!("[start_overlay] stream_request_ai: {}", c);
+        let result = client.stream_request_ai(context, move |c| {
+                debug!("[start_overlay] stream_request_ai: {}", redact(&c));
+                track_completion(Some(my_generation), |t| {
+                    if t.event.first_token_ms.is_none() {
+                        t.event.first_token_ms = Some(t.started.elapsed().as_millis() as i64);
+                    }
+                    t.shown.push_str(&c);
+                });
                 let mut candidate = CANDIDATE.write().unwrap();
                 if candidate.is_empty() && !c.trim().is_empty() {
                     resize_overlay_window(200.0, 40.0);
@@ -98,6 +183,12 @@ fn start_overlay(focused_input: element::FocusedInput) {
                 overlay::overlay::update_overlay(c);
             }, cancel_token_clone).await;
 
+        track_completion(Some(my_generation), |t| {
+            t.event.total_ms = Some(t.started.elapsed().as_millis() as i64);
+            if let Err(e) = &result {
+                t.event.error = Some(e.to_string());
+            }
+        });
         if let Err(e) = result {
             error!("[start_overlay] stream_request_ai failed: {}", e);
             if let Some(app_handle) = APP_HANDLE.lock().unwrap().as_ref() {
@@ -112,6 +203,21 @@ fn start_overlay(focused_input: element::FocusedInput) {
     *OVERLAY_TASK_HANDLE.lock().unwrap() = Some(handle);
 }
 
+// 用户在候选框中做出授权决定后, 若仍聚焦在该应用则按决定继续
+pub fn on_consent_decided(app: &str, decision: ConsentDecision) {
+    let focused_input = FORMER_FOCUSED_INPUT.read().unwrap().clone();
+    match focused_input {
+        Some(focused_input) if focused_input.window_element.app == app => {
+            if decision == ConsentDecision::Deny {
+                end_overlay();
+            } else {
+                start_overlay(focused_input);
+            }
+        }
+        _ => overlay::overlay::hide_overlay(),
+    }
+}
+
 fn end_overlay_stream_task() {
     if let Some(handle) = OVERLAY_TASK_HANDLE.lock().unwrap().take() {
         debug!("[end_overlay_stream_task] abort handle");
@@ -127,6 +233,13 @@ fn end_overlay() {
     set_input_state(false);
     overlay::overlay::hide_overlay();
     end_overlay_stream_task();
+    finish_completion();
+}
+
+// 用户主动关闭候选框(Esc), 记为拒绝
+fn dismiss_overlay() {
+    track_completion(None, |t| t.event.dismissed = true);
+    end_overlay();
 }
 
 fn select_candidate(num: i32) {
@@ -153,14 +266,40 @@ fn select_candidate(num: i32) {
     debug!("[select_candidate] call element::fill_input");
     let mut current_selected_candidate = SELECTED_CANDIDATE.write().unwrap();
     *current_selected_candidate = format!("{}{}", current_selected_candidate, selected_chars);
+    context::assembler::record_acceptance(&selected_chars);
+    track_completion(None, |t| {
+        t.event.accepted_chars += selected_num as i64;
+        if num == -1 {
+            t.event.full_accepts += 1;
+        } else {
+            t.event.partial_accepts += 1;
+        }
+    });
     element::fill_input(selected_chars);
     debug!("[select_candidate] call overlay::overlay::select_candidate");
     overlay::overlay::select_candidate(selected_num);
 }
 
 fn save_history(focused_input: &element::FocusedInput) {
+    if app_consent::is_denied(&focused_input.window_element.app) {
+        debug!("[save_history] app denied, skip: {}", focused_input.window_element.app);
+        return;
+    }
+    let field = (focused_input.window_element.id, focused_input.input_element.id.clone());
+    if focused_input.input_element.content.trim().is_empty() {
+        *CLEARED_FIELD.lock().unwrap() = Some(field);
+        return;
+    }
+    let new_message = {
+        let mut cleared = CLEARED_FIELD.lock().unwrap();
+        if cleared.as_ref() == Some(&field) {
+            *cleared = None;
+            true
+        } else {
+            false
+        }
+    };
     info!("[save_history] for app: {}", focused_input.window_element.app);
-    let mut conn = crate::db::conn::establish_connection();
     let input_history = context::history::history::InputHistory {
         window_id: focused_input.window_element.id,
         window_app: focused_input.window_element.app.clone(),
@@ -179,8 +318,9 @@ fn save_history(focused_input: &element::FocusedInput) {
         input_height: focused_input.input_element.height,
         input_content: focused_input.input_element.content.clone(),
         timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64,
+        new_message,
     };
-    context::history::history::save_history(&mut conn, &input_history);
+    crate::db::writer::save_history(input_history);
 }
 
 pub fn listen_input_state() {
@@ -208,7 +348,11 @@ pub fn listen_input_state() {
                             {
                                 *guard = Some(focused_input.clone());
                             } else if new_content != old_content {
-                                let restart_overlay = {
+                                // 先记入当前请求, 若需要重新请求, 新请求从这次的内容开始计数
+                                let content_chars = new_content.chars().count() as i64;
+                                track_completion(None, |t| t.content_chars = content_chars);
+                                // 内容末尾与候选开头吻合时不重新请求; 吻合部分超出已选中的字时说明用户照着候选在输入
+                                let (restart_overlay, typed_through) = {
                                     let candidate = CANDIDATE.read().unwrap();
                                     if !candidate.is_empty() {
                                         let selected_candidate = SELECTED_CANDIDATE.read().unwrap();
@@ -216,6 +360,7 @@ pub fn listen_input_state() {
                                         let candidate_chars: Vec<char> = full_candidate.chars().collect();
                                         let new_content_chars: Vec<char> = new_content.chars().collect();
                                         let mut matched = false;
+                                        let mut typed_through = false;
                                         for i in 0..candidate_chars.len() {
                                             if new_content_chars.len() <= i {
                                                 break;
@@ -224,14 +369,18 @@ pub fn listen_input_state() {
                                             let new_content_suffix: String = new_content_chars[new_content_chars.len()-i-1..].iter().collect();
                                             if new_content_suffix == candidate_prefix {
                                                 matched = true;
+                                                typed_through = i + 1 > selected_candidate.chars().count();
                                                 break;
                                             }
                                         }
-                                        !matched
+                                        (!matched, typed_through)
                                     } else {
-                                        true
+                                        (true, false)
                                     }
                                 };
+                                if typed_through {
+                                    track_completion(None, |t| t.event.typed_through = true);
+                                }
                                 debug!("[listen_input_state] restart_overlay: {}", restart_overlay);
                                 if restart_overlay {
                                     info!("[listen_input_state] content changed, restarting overlay");
@@ -256,6 +405,7 @@ pub fn listen_input_state() {
                     if let Some(former_focused_input) = &*guard {
                         info!("[listen_input_state] focus lost");
                         save_history(former_focused_input);
+                        *LAST_FOCUSED_INPUT.write().unwrap() = Some(former_focused_input.clone());
                     }
                     *guard = None;
                     debug!("[listen_input_state] focused_input: None");
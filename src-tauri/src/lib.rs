@@ -4,15 +4,18 @@ use config::{get_config_for_frontend, save_config_for_frontend};
 use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use os::{element};
-use tauri::{image::Image, menu::{MenuBuilder, MenuItemBuilder}, tray::{TrayIconBuilder, TrayIconEvent}, AppHandle, Emitter, Manager, WindowEvent};
+use tauri::{image::Image, menu::{CheckMenuItemBuilder, MenuBuilder, MenuItemBuilder}, tray::{TrayIconBuilder, TrayIconEvent}, AppHandle, Emitter, Manager, WindowEvent};
 use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
+use tauri_plugin_notification::NotificationExt;
 use utils::logger::init_logger;
 use windows::Win32::{System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED}};
 use raw_window_handle::{HasWindowHandle, RawWindowHandle};
 use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
 use windows::Win32::UI::WindowsAndMessaging::{
-    CallWindowProcW, SetWindowLongPtrW, GWLP_WNDPROC, WM_CLIPBOARDUPDATE, GetWindowLongPtrW, GWL_EXSTYLE, WS_EX_APPWINDOW, WS_EX_TOOLWINDOW
+    CallWindowProcW, SetWindowLongPtrW, GWLP_WNDPROC, WM_CLIPBOARDUPDATE, GetWindowLongPtrW, GWL_EXSTYLE, WS_EX_APPWINDOW, WS_EX_TOOLWINDOW,
+    MessageBoxW, MB_OK, MB_ICONERROR,
 };
+use windows::core::{w, PCWSTR};
 
 mod ai;
 mod input;
@@ -24,8 +27,17 @@ mod utils;
 mod overlay;
 
 static APP_HANDLE: Lazy<Mutex<Option<AppHandle>>> = Lazy::new(|| Mutex::new(None));
+const TRAY_ID: &str = "main";
 static mut ORIGINAL_WNDPROC: Option<unsafe extern "system" fn(HWND, u32, WPARAM, LPARAM) -> LRESULT> = None;
 
+fn tray_tooltip(local_only: bool) -> &'static str {
+  if local_only {
+      "ainput (local only)"
+  } else {
+      "ainput"
+  }
+}
+
 fn setup_tray(
   app_handle: &AppHandle,
   config: &config::Config,
@@ -38,21 +50,31 @@ fn setup_tray(
   let exit_item = MenuItemBuilder::with_id("exit", "Exit").build(app_handle)?;
   let restart_item = MenuItemBuilder::with_id("restart", "Restart").build(app_handle)?;
   let settings_item = MenuItemBuilder::with_id("settings", "Settings").build(app_handle)?;
+  let preview_item = MenuItemBuilder::with_id("preview", "Preview Request").build(app_handle)?;
+  let compact_item = MenuItemBuilder::with_id("compact_history", "Compact History").build(app_handle)?;
+  let local_only_item = CheckMenuItemBuilder::with_id("local_only", "Local Only")
+    .checked(config.privacy.local_only)
+    .build(app_handle)?;
 
   let tray_menu = MenuBuilder::new(app_handle)
     .item(&settings_item)
+    .item(&preview_item)
+    .item(&local_only_item)
+    .item(&compact_item)
     .item(&restart_item)
     .item(&exit_item)
     .build()?;
 
   let tray_icon = Image::from_bytes(include_bytes!("../icons/icon.ico"))?;
 
-  let _tray_icon = TrayIconBuilder::new()
+  let _tray_icon = TrayIconBuilder::with_id(TRAY_ID)
+      .tooltip(tray_tooltip(config.privacy.local_only))
       .menu(&tray_menu)
       .on_menu_event(move |tray_handle, event| {
           let app_handle = tray_handle.app_handle();
           match event.id.as_ref() {
               "exit" => {
+                  db::writer::flush_pending();
                   app_handle.exit(0);
               }
               "settings" => {
@@ -68,7 +90,40 @@ fn setup_tray(
                           .build();
                   }
               }
+              "preview" => {
+                  let label = "preview";
+                  if let Some(window) = app_handle.get_webview_window(label) {
+                      window.show().unwrap();
+                      window.set_focus().unwrap();
+                  } else {
+                      let _ = tauri::WebviewWindowBuilder::new(app_handle, label, tauri::WebviewUrl::App("/preview".into()))
+                          .title("AInput Request Preview")
+                          .resizable(true)
+                          .inner_size(600.0, 600.0)
+                          .build();
+                  }
+              }
+              "local_only" => {
+                  let local_only = !ai::egress::is_local_only();
+                  ai::egress::set_local_only(local_only);
+                  if let Some(tray) = app_handle.tray_by_id(TRAY_ID) {
+                      let _ = tray.set_tooltip(Some(tray_tooltip(local_only)));
+                  }
+              }
+              "compact_history" => {
+                  let app_handle = app_handle.clone();
+                  std::thread::spawn(move || {
+                      let mut conn = db::conn::establish_connection();
+                      let body = match context::history::history::compact_history(&mut conn) {
+                          Ok(n) => format!("Merged {} draft snapshots", n),
+                          Err(e) => format!("Failed to compact history: {}", e),
+                      };
+                      info!("[setup_tray] {}", body);
+                      let _ = app_handle.notification().builder().title("ainput").body(body).show();
+                  });
+              }
               "restart" => {
+                  db::writer::flush_pending();
                   app_handle.restart();
               }
               _ => {}
@@ -114,6 +169,16 @@ fn set_auto_start(
   Ok(())
 }
 
+// 启动阶段无法继续时弹窗提示并退出, 此时窗口和托盘都还没有创建
+fn fatal_startup_error(message: &str) -> ! {
+  error!("[fatal_startup_error] {}", message);
+  let text: Vec<u16> = format!("ainput failed to start:\n{}", message).encode_utf16().chain(Some(0)).collect();
+  unsafe {
+      MessageBoxW(None, PCWSTR(text.as_ptr()), w!("ainput"), MB_OK | MB_ICONERROR);
+  }
+  std::process::exit(1);
+}
+
 fn setup_panic_handler(app_handle: tauri::AppHandle) {
   panic::set_hook(Box::new(move |panic_info| {
       let location = panic_info
@@ -169,6 +234,28 @@ fn create_app_builder() -> tauri::Builder<tauri::Wry> {
           overlay::overlay::resize_overlay_window,
           overlay::overlay::get_overlay_style,
           db::ai_token_usage::get_used_token_command,
+          ai::ai_client::preview_request_command,
+          db::privacy_audit::get_privacy_audit_command,
+          db::privacy_audit::export_privacy_audit_command,
+          db::completion_event::get_completion_events_command,
+          db::stats::get_stats_command,
+          ai::egress::get_local_only_command,
+          db::app_consent::set_app_consent_command,
+          db::app_consent::get_app_consents_command,
+          db::app_consent::delete_app_consent_command,
+          db::vault::list_vault_command,
+          context::history::search::rebuild_search_index_command,
+          context::history::history::compact_history_command,
+          context::history::browser::list_history_command,
+          context::history::browser::get_history_apps_command,
+          context::history::browser::delete_history_command,
+          context::history::browser::delete_app_history_command,
+          context::history::browser::update_history_command,
+          context::history::browser::pin_history_command,
+          context::history::retention::purge_history_command,
+          db::vault::delete_vault_entry_command,
+          db::archive::export_data_command,
+          db::archive::import_data_command,
       ])
       .on_window_event(|window, event| {
           if let WindowEvent::CloseRequested { api, .. } = event {
@@ -201,21 +288,49 @@ unsafe extern "system" fn sub_wnd_proc(
 
 pub fn run() {
   info!("[run] starting ainput application");
-  // 自动切换到 exe 所在目录, 为了解决windows自动启动时workding directory读取不到配置文件的问题
-  if !cfg!(debug_assertions) {
-      if let Ok(exe_path) = std::env::current_exe() {
-          if let Some(exe_dir) = exe_path.parent() {
-              let _ = std::env::set_current_dir(exe_dir);
-          }
-      }
-  }
   // Initialize config first
   config::init_config();
   let config = config::get_config().unwrap();
   let config_for_manage = config.clone();
 
+  // 数据目录: --data-dir, AINPUT_DATA_DIR, storage.data_dir, %APPDATA%\ainput(便携模式为 exe 所在目录)
+  if let Err(e) = utils::data_dir::init(&config.storage.data_dir) {
+      fatal_startup_error(&e);
+  }
+
   // Initialize logger
-  let _ = init_logger(config.system.logging_level.clone());
+  let _ = init_logger(config.system.logging_level.clone(), config.system.diagnostic_logging);
+  info!("[run] data directory: {:?}", utils::data_dir::dir());
+
+  // 旧版本的数据在 exe 旁(开发时为工作目录), 迁移到数据目录
+  let legacy_files = [config.storage.keyfile.as_str(), db::vault::VAULT_FILE, config.privacy.vault_keyfile.as_str(), "hash.salt"];
+  if let Err(e) = utils::data_dir::migrate_legacy(&legacy_files) {
+      fatal_startup_error(&format!("failed to migrate data to {:?}: {}", utils::data_dir::dir(), e));
+  }
+
+  // 按配置加密 input.db, 已有明文数据库会迁移为加密数据库
+  if let Err(e) = db::encryption::init(&db::conn::db_path()) {
+      fatal_startup_error(&format!("failed to initialize database encryption: {}", e));
+  }
+
+  // 数据库结构迁移, 失败时不继续启动, 避免在不完整的结构上读写
+  if let Err(e) = db::migrations::init() {
+      fatal_startup_error(&format!("failed to migrate database: {}", e));
+  }
+
+  if let Err(e) = db::conn::init_pool() {
+      fatal_startup_error(&format!("failed to open database: {}", e));
+  }
+  db::app_consent::load_cache(&mut db::conn::establish_connection());
+  // 历史、用量和审计统一由写线程落盘
+  db::writer::start();
+
+  // 命令行导出/导入, 完成后退出
+  let args: Vec<String> = std::env::args().collect();
+  if let Some(code) = db::archive::run_cli(&args) {
+      db::writer::flush_pending();
+      std::process::exit(code);
+  }
   
   // Initialize COM
   unsafe {
@@ -287,10 +402,20 @@ pub fn run() {
       set_auto_start(&app_handle, &config).expect("Failed to setup auto start");
       info!("[✓] auto start setup");
 
-    //   // Initialize Tantivy search index
-    //   let app_data_dir = app_handle.path().app_data_dir().expect("Failed to get app data dir");
-    //   init_search_index(app_data_dir);
-    //   info!("[✓] Tantivy search index initialized");
+      // 历史全文索引
+      context::history::search::init();
+      info!("[✓] search index initialized");
+
+      // 历史保留策略, 后台定期清理
+      context::history::retention::start_scheduler();
+      info!("[✓] retention scheduler started");
+
+      // 定期使用摘要通知
+      db::stats::start_summary_scheduler();
+      info!("[✓] stats summary scheduler started");
+
+      context::source::knowledge::start_watcher();
+      info!("[✓] knowledge watcher started");
 
       *APP_HANDLE.lock().unwrap() = Some(app_handle.clone());
       info!("=== application initialized ===");
@@ -307,6 +432,8 @@ pub fn run() {
   app.run(|_app_handle, event| {
       if let tauri::RunEvent::Exit = event {
           info!("application is exiting, cleaning up resources...");
+          db::writer::flush_pending();
+          info!("[✓] pending database writes flushed");
           input::hook::cleanup();
           info!("[✓] keyboard listener cleaned up");
 
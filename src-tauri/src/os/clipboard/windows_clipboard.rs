@@ -1,6 +1,8 @@
 use std::collections::VecDeque;
 use std::sync::Mutex;
-use windows::Win32::System::DataExchange::{OpenClipboard, CloseClipboard, GetClipboardData, AddClipboardFormatListener, SetClipboardData, EmptyClipboard};
+use log::info;
+use windows::core::w;
+use windows::Win32::System::DataExchange::{OpenClipboard, CloseClipboard, GetClipboardData, AddClipboardFormatListener, SetClipboardData, EmptyClipboard, GetClipboardOwner, IsClipboardFormatAvailable, RegisterClipboardFormatW};
 use windows::Win32::Foundation::{HWND, HANDLE, HGLOBAL};
 use windows::Win32::System::Memory::{GlobalLock, GlobalUnlock, GlobalAlloc, GMEM_MOVEABLE};
 use std::ffi::OsString;
@@ -15,32 +17,56 @@ const CF_UNICODETEXT: u32 = 13; // Win32 剪贴板 Unicode 文本格式
 
 static CLIPBOARD_HISTORY: Lazy<Mutex<ClipboardHistory>> = Lazy::new(|| Mutex::new(ClipboardHistory::new()));
 
+#[derive(Debug, Clone)]
+pub struct ClipboardEntry {
+    pub text: String,
+    // 复制来源应用
+    pub app: String,
+    pub timestamp: i64,
+}
+
 pub struct ClipboardHistory {
-    history: VecDeque<String>,
+    history: VecDeque<ClipboardEntry>,
     total_size: usize,
 }
 
+fn now_millis() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64
+}
+
 impl ClipboardHistory {
     pub fn new() -> Self {
         Self { history: VecDeque::new(), total_size: 0 }
     }
-    pub fn add(&mut self, text: String) {
+    pub fn add(&mut self, text: String, app: String) {
         let mut text = text;
         if text.len() > PER_ITEM_LIMIT {
             text.truncate(PER_ITEM_LIMIT);
         }
-        if self.history.back().map_or(false, |last| last == &text) {
+        if self.history.back().map_or(false, |last| last.text == text) {
             return;
         }
         self.total_size += text.len();
-        self.history.push_back(text);
+        self.history.push_back(ClipboardEntry { text, app, timestamp: now_millis() });
         while self.total_size > TOTAL_LIMIT {
             if let Some(removed) = self.history.pop_front() {
-                self.total_size -= removed.len();
+                self.total_size -= removed.text.len();
+            }
+        }
+    }
+    // 移除超过 max_age 秒的记录
+    pub fn expire(&mut self, max_age: u64) {
+        if max_age == 0 {
+            return;
+        }
+        let expire = now_millis() - (max_age as i64) * 1000;
+        while self.history.front().map_or(false, |first| first.timestamp < expire) {
+            if let Some(removed) = self.history.pop_front() {
+                self.total_size -= removed.text.len();
             }
         }
     }
-    pub fn get_all(&self) -> Vec<String> {
+    pub fn get_all(&self) -> Vec<ClipboardEntry> {
         self.history.iter().cloned().collect()
     }
 }
@@ -73,16 +99,73 @@ pub fn register_clipboard_listener(hwnd: HWND) -> bool {
     unsafe { AddClipboardFormatListener(hwnd).is_ok() }
 }
 
+// 来源应用是否标记了该内容不应被剪贴板历史/监控程序记录(如密码管理器)
+fn is_flagged_sensitive() -> bool {
+    unsafe {
+        let exclude = RegisterClipboardFormatW(w!("ExcludeClipboardContentFromMonitorProcessing"));
+        let viewer_ignore = RegisterClipboardFormatW(w!("Clipboard Viewer Ignore"));
+        if (exclude != 0 && IsClipboardFormatAvailable(exclude).is_ok())
+            || (viewer_ignore != 0 && IsClipboardFormatAvailable(viewer_ignore).is_ok()) {
+            return true;
+        }
+        // CanIncludeInClipboardHistory 为 0 表示不应进入剪贴板历史
+        let can_include = RegisterClipboardFormatW(w!("CanIncludeInClipboardHistory"));
+        if can_include == 0 || IsClipboardFormatAvailable(can_include).is_err() {
+            return false;
+        }
+        let mut flagged = false;
+        if OpenClipboard(None).is_ok() {
+            if let Ok(handle) = GetClipboardData(can_include) {
+                let ptr = GlobalLock(HGLOBAL(handle.0));
+                if !ptr.is_null() {
+                    flagged = *(ptr as *const u32) == 0;
+                    let _ = GlobalUnlock(HGLOBAL(handle.0));
+                }
+            }
+            CloseClipboard().ok();
+        }
+        flagged
+    }
+}
+
+fn get_clipboard_owner_app() -> String {
+    unsafe {
+        match GetClipboardOwner() {
+            Ok(hwnd) if !hwnd.0.is_null() => crate::os::window::get_window_app(hwnd),
+            _ => String::new(),
+        }
+    }
+}
+
 // 在窗口消息循环中处理WM_CLIPBOARDUPDATE
 pub fn handle_clipboard_update() {
+    let clipboard_config = crate::config::get_config().unwrap().clipboard;
+    let app = get_clipboard_owner_app();
+    if clipboard_config.ignore_apps.contains(&app) {
+        info!("[handle_clipboard_update] skip clipboard from ignored app: {}", app);
+        return;
+    }
+    if is_flagged_sensitive() {
+        info!("[handle_clipboard_update] skip clipboard flagged sensitive by source app: {}", app);
+        return;
+    }
     if let Some(text) = get_clipboard_text() {
+        if clipboard_config.exclude_sensitive && crate::ai::privacy::is_sensitive(&text) {
+            info!("[handle_clipboard_update] skip clipboard matching privacy rules from app: {}", app);
+            return;
+        }
+        let text = crate::ai::privacy::redact_for_storage(&text);
         let mut hist = CLIPBOARD_HISTORY.lock().unwrap();
-        hist.add(text);
+        hist.expire(clipboard_config.max_age);
+        hist.add(text, app);
     }
 }
 
-pub fn get_clipboard_history() -> Vec<String> {
-    CLIPBOARD_HISTORY.lock().unwrap().get_all() 
+pub fn get_clipboard_history() -> Vec<ClipboardEntry> {
+    let max_age = crate::config::get_config().unwrap().clipboard.max_age;
+    let mut hist = CLIPBOARD_HISTORY.lock().unwrap();
+    hist.expire(max_age);
+    hist.get_all()
 }
 
 pub fn set_clipboard_text(text: &str) -> bool {
@@ -1,5 +1,6 @@
 pub mod ui_automation;
 pub mod app_element;
+pub mod nearby;
 use log::debug;
 pub use ui_automation::FocusedInput;
 
@@ -0,0 +1,346 @@
+//! 聚焦输入框周围的可见文本: 关联标签、帮助文本(占位符)、兄弟元素和上层面板中的内容
+//! 遍历逻辑只依赖 ElementNode, 可以用假的元素树代替 UI Automation
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+pub enum NearbyKind {
+    // LabeledBy 指向的标签
+    Label,
+    // 帮助文本, 多数应用的占位符也通过它暴露
+    HelpText,
+    Sibling,
+    // 上层面板子树中的文本, 如聊天窗口中输入框上方的消息
+    Pane,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NearbyText {
+    pub kind: NearbyKind,
+    pub text: String,
+}
+
+pub trait ElementNode: Sized {
+    fn name(&self) -> String;
+    fn help_text(&self) -> String;
+    fn value(&self) -> String;
+    fn labeled_by(&self) -> Option<Self>;
+    fn parent(&self) -> Option<Self>;
+    fn children(&self) -> Vec<Self>;
+    fn same_as(&self, other: &Self) -> bool;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NearbyLimits {
+    // 最多收集的文本条数
+    pub max_items: usize,
+    // 所有文本合计的最大字符数, 超出时保留靠后(离输入框更近)的部分
+    pub max_chars: usize,
+    // 向上取几层父元素作为面板
+    pub pane_levels: usize,
+    // 兄弟元素和面板子树合计最多访问的元素数
+    pub max_nodes: usize,
+}
+
+struct Collector {
+    texts: Vec<NearbyText>,
+    seen: HashSet<String>,
+    visited: usize,
+    max_nodes: usize,
+}
+
+impl Collector {
+    fn push(&mut self, kind: NearbyKind, text: String) {
+        let text = text.trim().to_string();
+        if text.is_empty() || !self.seen.insert(text.clone()) {
+            return;
+        }
+        self.texts.push(NearbyText { kind, text });
+    }
+
+    fn push_node<N: ElementNode>(&mut self, kind: NearbyKind, node: &N) {
+        self.push(kind, node.name());
+        self.push(kind, node.value());
+    }
+
+    fn take_node(&mut self) -> bool {
+        if self.visited >= self.max_nodes {
+            return false;
+        }
+        self.visited += 1;
+        true
+    }
+
+    // 输入框之前的子树按文档逆序访问, 先收集离输入框最近的末尾元素
+    fn visit_backward<N: ElementNode>(&mut self, kind: NearbyKind, node: &N) {
+        if !self.take_node() {
+            return;
+        }
+        for child in node.children().iter().rev() {
+            self.visit_backward(NearbyKind::Pane, child);
+        }
+        self.push_node(kind, node);
+    }
+
+    // 输入框之后的子树按文档顺序访问
+    fn visit_forward<N: ElementNode>(&mut self, kind: NearbyKind, node: &N) {
+        if !self.take_node() {
+            return;
+        }
+        self.push_node(kind, node);
+        for child in node.children() {
+            self.visit_forward(NearbyKind::Pane, &child);
+        }
+    }
+}
+
+pub fn collect_nearby<N: ElementNode>(focused: &N, limits: &NearbyLimits) -> Vec<NearbyText> {
+    let mut collector = Collector { texts: Vec::new(), seen: HashSet::new(), visited: 0, max_nodes: limits.max_nodes };
+    // 输入框自身的名称和内容已在 prompt 中, 不重复收集
+    collector.seen.insert(focused.name().trim().to_string());
+    collector.seen.insert(focused.value().trim().to_string());
+
+    if let Some(label) = focused.labeled_by() {
+        collector.push(NearbyKind::Label, label.name());
+    }
+    collector.push(NearbyKind::HelpText, focused.help_text());
+
+    // 从父元素开始向外逐层遍历 pane_levels 层, max_nodes 优先用在离输入框近的元素上:
+    // 每层先由近及远访问路径之前的兄弟子树(如输入框上方的消息), 再访问之后的兄弟子树, 最后是该层祖先自身
+    // 第一层的兄弟元素记为 Sibling, 其余记为 Pane
+    let mut path: Option<N> = None;
+    let mut ancestor = focused.parent();
+    for level in 0..limits.pane_levels.max(1) {
+        let Some(node) = ancestor else { break };
+        let kind = if level == 0 { NearbyKind::Sibling } else { NearbyKind::Pane };
+        let children = node.children();
+        let on_path = path.as_ref().unwrap_or(focused);
+        let (before, after) = match children.iter().position(|c| c.same_as(on_path)) {
+            Some(pos) => (&children[..pos], &children[pos + 1..]),
+            None => (&children[..], &children[children.len()..]),
+        };
+        for sibling in before.iter().rev() {
+            collector.visit_backward(kind, sibling);
+        }
+        for sibling in after {
+            collector.visit_forward(kind, sibling);
+        }
+        if collector.take_node() {
+            collector.push_node(NearbyKind::Pane, &node);
+        }
+        ancestor = node.parent();
+        path = Some(node);
+    }
+
+    let mut texts = collector.texts;
+    // 标签和帮助文本排在最前, 总会保留; 其余按由远及近排列, 超出上限时丢弃靠前(较远)的
+    let fixed = texts.iter().take_while(|t| matches!(t.kind, NearbyKind::Label | NearbyKind::HelpText)).count();
+    let mut rest = texts.split_off(fixed);
+    rest.reverse();
+    let mut chars: usize = texts.iter().map(|t| t.text.chars().count()).sum();
+    let mut kept = Vec::new();
+    while let Some(t) = rest.pop() {
+        let len = t.text.chars().count();
+        if texts.len() + kept.len() >= limits.max_items || chars + len > limits.max_chars {
+            break;
+        }
+        chars += len;
+        kept.push(t);
+    }
+    kept.reverse();
+    texts.extend(kept);
+    texts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct FakeData {
+        name: String,
+        help_text: String,
+        value: String,
+        parent: Option<usize>,
+        children: Vec<usize>,
+        labeled_by: Option<usize>,
+    }
+
+    #[derive(Clone)]
+    struct FakeNode {
+        tree: Rc<Vec<FakeData>>,
+        id: usize,
+    }
+
+    impl FakeNode {
+        fn data(&self) -> &FakeData {
+            &self.tree[self.id]
+        }
+
+        fn at(&self, id: usize) -> Self {
+            FakeNode { tree: self.tree.clone(), id }
+        }
+    }
+
+    impl ElementNode for FakeNode {
+        fn name(&self) -> String {
+            self.data().name.clone()
+        }
+
+        fn help_text(&self) -> String {
+            self.data().help_text.clone()
+        }
+
+        fn value(&self) -> String {
+            self.data().value.clone()
+        }
+
+        fn labeled_by(&self) -> Option<Self> {
+            self.data().labeled_by.map(|id| self.at(id))
+        }
+
+        fn parent(&self) -> Option<Self> {
+            self.data().parent.map(|id| self.at(id))
+        }
+
+        fn children(&self) -> Vec<Self> {
+            self.data().children.iter().map(|id| self.at(*id)).collect()
+        }
+
+        fn same_as(&self, other: &Self) -> bool {
+            self.id == other.id
+        }
+    }
+
+    fn add(tree: &mut Vec<FakeData>, parent: Option<usize>, name: &str) -> usize {
+        let id = tree.len();
+        tree.push(FakeData { name: name.to_string(), parent, ..Default::default() });
+        if let Some(parent) = parent {
+            tree[parent].children.push(id);
+        }
+        id
+    }
+
+    // 聊天窗口: 上方是消息列表, 下方是带标签的输入框和发送按钮
+    fn chat_window() -> FakeNode {
+        let mut tree = Vec::new();
+        let root = add(&mut tree, None, "Chat");
+        let history = add(&mut tree, Some(root), "History");
+        add(&mut tree, Some(history), "hello");
+        add(&mut tree, Some(history), "how are you");
+        let composer = add(&mut tree, Some(root), "");
+        let label = add(&mut tree, Some(composer), "Message to Bob");
+        let input = add(&mut tree, Some(composer), "Message");
+        add(&mut tree, Some(composer), "Send");
+        tree[input].value = "I am".to_string();
+        tree[input].help_text = "Type a message".to_string();
+        tree[input].labeled_by = Some(label);
+        FakeNode { tree: Rc::new(tree), id: input }
+    }
+
+    fn limits() -> NearbyLimits {
+        NearbyLimits { max_items: 40, max_chars: 2000, pane_levels: 2, max_nodes: 200 }
+    }
+
+    fn texts(nearby: &[NearbyText]) -> Vec<(NearbyKind, &str)> {
+        nearby.iter().map(|t| (t.kind, t.text.as_str())).collect()
+    }
+
+    #[test]
+    fn collects_label_hint_siblings_and_pane_in_order() {
+        let nearby = collect_nearby(&chat_window(), &limits());
+        assert_eq!(
+            texts(&nearby),
+            vec![
+                (NearbyKind::Label, "Message to Bob"),
+                (NearbyKind::HelpText, "Type a message"),
+                (NearbyKind::Pane, "Chat"),
+                (NearbyKind::Pane, "History"),
+                (NearbyKind::Pane, "hello"),
+                (NearbyKind::Pane, "how are you"),
+                (NearbyKind::Sibling, "Send"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_focused_element_and_duplicates() {
+        let nearby = collect_nearby(&chat_window(), &limits());
+        assert!(nearby.iter().all(|t| t.text != "Message" && t.text != "I am"));
+        assert_eq!(nearby.iter().filter(|t| t.text == "Send").count(), 1);
+    }
+
+    #[test]
+    fn pane_levels_bounds_the_walk_upwards() {
+        let nearby = collect_nearby(&chat_window(), &NearbyLimits { pane_levels: 1, ..limits() });
+        assert_eq!(
+            texts(&nearby),
+            vec![
+                (NearbyKind::Label, "Message to Bob"),
+                (NearbyKind::HelpText, "Type a message"),
+                (NearbyKind::Sibling, "Send"),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_items_keeps_label_and_texts_closest_to_input() {
+        let nearby = collect_nearby(&chat_window(), &NearbyLimits { max_items: 4, ..limits() });
+        assert_eq!(
+            texts(&nearby),
+            vec![
+                (NearbyKind::Label, "Message to Bob"),
+                (NearbyKind::HelpText, "Type a message"),
+                (NearbyKind::Pane, "how are you"),
+                (NearbyKind::Sibling, "Send"),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_chars_drops_earlier_texts() {
+        let fixed = "Message to Bob".len() + "Type a message".len();
+        let nearby = collect_nearby(&chat_window(), &NearbyLimits { max_chars: fixed + "how are you".len() + "Send".len(), ..limits() });
+        assert_eq!(texts(&nearby[2..]), vec![(NearbyKind::Pane, "how are you"), (NearbyKind::Sibling, "Send")]);
+    }
+
+    #[test]
+    fn max_nodes_stops_the_pane_walk() {
+        let nearby = collect_nearby(&chat_window(), &NearbyLimits { max_nodes: 2, ..limits() });
+        assert!(nearby.iter().all(|t| t.kind != NearbyKind::Pane));
+    }
+
+    // 消息列表比 max_nodes 长时, 访问预算用在输入框上方最近的消息上
+    #[test]
+    fn max_nodes_keeps_messages_closest_to_input() {
+        let mut tree = Vec::new();
+        let root = add(&mut tree, None, "Chat");
+        let history = add(&mut tree, Some(root), "History");
+        for i in 0..50 {
+            add(&mut tree, Some(history), &format!("message {}", i));
+        }
+        let composer = add(&mut tree, Some(root), "");
+        let input = add(&mut tree, Some(composer), "Message");
+        add(&mut tree, Some(composer), "Send");
+        let focused = FakeNode { tree: Rc::new(tree), id: input };
+
+        // Send、composer、History 各占一个, 其余 7 个给最后的消息
+        let nearby = collect_nearby(&focused, &NearbyLimits { max_nodes: 10, ..limits() });
+        let expected: Vec<String> = (43..50).map(|i| format!("message {}", i)).collect();
+        let pane: Vec<&str> = nearby.iter().filter(|t| t.text.starts_with("message")).map(|t| t.text.as_str()).collect();
+        assert_eq!(pane, expected);
+        assert_eq!(nearby.last().map(|t| t.text.as_str()), Some("Send"));
+    }
+
+    #[test]
+    fn element_without_parent_has_only_own_hints() {
+        let mut tree = Vec::new();
+        let input = add(&mut tree, None, "Search");
+        tree[input].help_text = "Search files".to_string();
+        let nearby = collect_nearby(&FakeNode { tree: Rc::new(tree), id: input }, &limits());
+        assert_eq!(texts(&nearby), vec![(NearbyKind::HelpText, "Search files")]);
+    }
+}
@@ -1,9 +1,14 @@
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
 use log::{debug, error, info};
 use serde::Serialize;
 use windows::Win32::{ System::Com::*, UI::Accessibility::*};
 use windows::core::Interface;
 
 use crate::os::{window, WindowElement};
+use super::nearby::{collect_nearby, ElementNode, NearbyLimits, NearbyText};
+use crate::utils::logger::{redact, redact_debug};
 
 #[derive(Clone, Debug, Serialize)]
 pub struct UIElement {
@@ -25,6 +30,8 @@ pub struct UIElement {
 pub struct FocusedInput {
     pub window_element: WindowElement,
     pub input_element: UIElement,
+    // 输入框周围的文本, 与输入框取自同一个元素
+    pub nearby: Vec<NearbyText>,
 }
 
 impl Eq for FocusedInput {}
@@ -40,6 +47,44 @@ impl PartialEq for FocusedInput {
     }
 }
 
+// 上次收集周围文本的元素和收集时间; 焦点轮询很频繁, 输入时内容每次都变,
+// 同一元素在 NEARBY_TTL 内沿用上次的结果, 不在每次按键时遍历元素树
+thread_local! {
+    static LAST_NEARBY: RefCell<Option<(IUIAutomationElement, Instant, Vec<NearbyText>)>> = const { RefCell::new(None) };
+}
+const NEARBY_TTL: Duration = Duration::from_secs(3);
+
+fn nearby_limits() -> NearbyLimits {
+    let config = crate::config::get_config().unwrap().ui_automation;
+    NearbyLimits {
+        max_items: config.nearby_max_items,
+        max_chars: config.nearby_max_chars,
+        pane_levels: config.nearby_pane_levels,
+        max_nodes: config.nearby_max_nodes,
+    }
+}
+
+unsafe fn nearby_texts(automation: &IUIAutomation, element: &IUIAutomationElement) -> Vec<NearbyText> {
+    let cached = LAST_NEARBY.with(|last| {
+        last.borrow().as_ref().and_then(|(last_element, collected_at, texts)| {
+            let same = collected_at.elapsed() < NEARBY_TTL
+                && automation.CompareElements(last_element, element).map(|b| b.as_bool()).unwrap_or(false);
+            same.then(|| texts.clone())
+        })
+    });
+    if let Some(texts) = cached {
+        return texts;
+    }
+    let Ok(walker) = automation.ControlViewWalker() else {
+        return Vec::new();
+    };
+    let focused = UiaNode { element: element.clone(), automation: automation.clone(), walker };
+    let texts = collect_nearby(&focused, &nearby_limits());
+    debug!("[nearby_texts] collected {} texts", texts.len());
+    LAST_NEARBY.with(|last| *last.borrow_mut() = Some((element.clone(), Instant::now(), texts.clone())));
+    texts
+}
+
 /// 获取当前聚焦输入框及其在窗口内的相对位置
 pub fn get_focused_input() -> Option<FocusedInput> {
     unsafe {
@@ -63,8 +108,8 @@ pub fn get_focused_input() -> Option<FocusedInput> {
             }
         }
         if !super::app_element::is_edit_element_in_app(&window_element.app, &window_element.class_name, &text, control_type.0, &content) {
-            debug!("[get_focused_input] not edit, current window: {:?}, input_text: {}, control_type: {}, content: {}",
-                window_element, text, control_type.0, content);
+            debug!("[get_focused_input] not edit, current window: {}, input_text: {}, control_type: {}, content: {}",
+                redact_debug(&window_element), redact(&text), control_type.0, redact(&content));
             return None;
         }
         
@@ -83,16 +128,75 @@ pub fn get_focused_input() -> Option<FocusedInput> {
             element_type: 0,
             content,
         };
-        debug!("[get_focused_input] found focused input in app: {}, current window: {:?}, current input: {:?}, ", window_element.app, window_element, input_element);
-        Some(FocusedInput { window_element, input_element })
+        let nearby = nearby_texts(&automation, &focused);
+        debug!("[get_focused_input] found focused input in app: {}, current window: {}, current input: {}, ", window_element.app, redact_debug(&window_element), redact_debug(&input_element));
+        Some(FocusedInput { window_element, input_element, nearby })
+    }
+}
+
+struct UiaNode {
+    element: IUIAutomationElement,
+    automation: IUIAutomation,
+    walker: IUIAutomationTreeWalker,
+}
+
+impl UiaNode {
+    fn wrap(&self, element: IUIAutomationElement) -> Self {
+        UiaNode { element, automation: self.automation.clone(), walker: self.walker.clone() }
+    }
+}
+
+impl ElementNode for UiaNode {
+    fn name(&self) -> String {
+        unsafe { self.element.CurrentName().map(|s| s.to_string()).unwrap_or_default() }
+    }
+
+    fn help_text(&self) -> String {
+        unsafe { self.element.CurrentHelpText().map(|s| s.to_string()).unwrap_or_default() }
+    }
+
+    fn value(&self) -> String {
+        unsafe {
+            self.element
+                .GetCurrentPattern(UIA_ValuePatternId)
+                .ok()
+                .and_then(|p| p.cast::<IUIAutomationValuePattern>().ok())
+                .and_then(|p| p.CurrentValue().ok())
+                .map(|v| v.to_string())
+                .unwrap_or_default()
+        }
+    }
+
+    fn labeled_by(&self) -> Option<Self> {
+        unsafe { self.element.CurrentLabeledBy().ok().map(|e| self.wrap(e)) }
+    }
+
+    fn parent(&self) -> Option<Self> {
+        unsafe { self.walker.GetParentElement(&self.element).ok().map(|e| self.wrap(e)) }
+    }
+
+    fn children(&self) -> Vec<Self> {
+        let mut children = Vec::new();
+        unsafe {
+            let mut child = self.walker.GetFirstChildElement(&self.element).ok();
+            while let Some(element) = child {
+                child = self.walker.GetNextSiblingElement(&element).ok();
+                children.push(self.wrap(element));
+            }
+        }
+        children
+    }
+
+    fn same_as(&self, other: &Self) -> bool {
+        unsafe { self.automation.CompareElements(&self.element, &other.element).map(|b| b.as_bool()).unwrap_or(false) }
     }
 }
 
 pub fn fill_input(focused_input: FocusedInput, selected_chars: String) {
-    debug!("[ui_automation::fill_input] selected_chars: {}", selected_chars);
+    debug!("[ui_automation::fill_input] selected_chars: {}", redact(&selected_chars));
     let current_content = focused_input.input_element.content.clone();
     let new_content = current_content + &selected_chars;
-    debug!("[ui_automation::fill_input] new_content: {}", new_content);
+    debug!("[ui_automation::fill_input] new_content: {}", redact(&new_content));
     unsafe {
         let automation = CoCreateInstance::<_, IUIAutomation>(&CUIAutomation, None, CLSCTX_ALL).ok();
         if let Some(automation) = automation {
@@ -1,4 +1,4 @@
 // Windows 窗口相关模块 
 pub mod windows_window;
 
-pub use windows_window::{WindowElement, get_current_window};
\ No newline at end of file
+pub use windows_window::{WindowElement, get_current_window, get_window_app};
\ No newline at end of file
@@ -1,7 +1,7 @@
 use log::{error};
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
-use windows::Win32::Foundation::RECT;
+use windows::Win32::Foundation::{HWND, RECT};
 use windows::Win32::UI::WindowsAndMessaging::{
     GetClassNameW, GetForegroundWindow, GetWindowRect, GetWindowTextW, IsWindowVisible
 };
@@ -39,6 +39,39 @@ impl PartialEq for WindowElement {
 
 impl Eq for WindowElement {}
 
+// 窗口所属进程的可执行文件名
+pub fn get_window_app(hwnd: HWND) -> String {
+    unsafe {
+        let mut process_id = 0u32;
+        GetWindowThreadProcessId(hwnd, Some(&mut process_id as *mut u32));
+        let process_handle = OpenProcess(
+            PROCESS_QUERY_INFORMATION | PROCESS_VM_READ,
+            false,
+            process_id,
+        );
+        let mut exe_name = String::from("");
+        if let Ok(process_handle) = process_handle {
+            let mut exe_path = [0u16; 512];
+            let len = K32GetModuleFileNameExW(
+                Some(process_handle),
+                None,
+                &mut exe_path,
+            ) as usize;
+            if len > 0 {
+                let os_string = OsString::from_wide(&exe_path[..len]);
+                let path = Path::new(&os_string);
+                if let Some(file_name) = path.file_name() {
+                    exe_name = file_name.to_string_lossy().to_string();
+                } else {
+                    exe_name = os_string.to_string_lossy().to_string();
+                }
+            }
+            let _ = CloseHandle(process_handle);
+        }
+        exe_name
+    }
+}
+
 pub fn get_current_window() -> Option<WindowElement> {
     unsafe {
         // 1. 获取前台窗口句柄
@@ -61,32 +94,7 @@ pub fn get_current_window() -> Option<WindowElement> {
                 &class_name[..class_name.iter().position(|&x| x == 0).unwrap_or(0)],
             );
 
-            let mut process_id = 0u32;
-            GetWindowThreadProcessId(hwnd, Some(&mut process_id as *mut u32));
-            let process_handle = OpenProcess(
-                PROCESS_QUERY_INFORMATION | PROCESS_VM_READ,
-                false,
-                process_id,
-            );
-            let mut exe_name = String::from("");
-            if let Ok(process_handle) = process_handle {
-                let mut exe_path = [0u16; 512];
-                let len = K32GetModuleFileNameExW(
-                    Some(process_handle),
-                    None,
-                    &mut exe_path,
-                ) as usize;
-                if len > 0 {
-                    let os_string = OsString::from_wide(&exe_path[..len]);
-                    let path = Path::new(&os_string);
-                    if let Some(file_name) = path.file_name() {
-                        exe_name = file_name.to_string_lossy().to_string();
-                    } else {
-                        exe_name = os_string.to_string_lossy().to_string();
-                    }
-                }
-                let _ = CloseHandle(process_handle);
-            }
+            let exe_name = get_window_app(hwnd);
 
             let window_element = WindowElement {
                 id: hwnd.0 as i64,
@@ -15,6 +15,22 @@ pub fn update_overlay(candidate: String) {
     }
 }
 
+// 在候选框中询问是否允许该应用使用补全
+pub fn request_consent(app: &str) {
+    info!("[request_consent] requesting consent for app: {}", app);
+    if let Some(window) = get_main_window() {
+        let _ = window.emit("consent_request", app.to_string());
+    }
+    resize_overlay_window(360.0, 40.0);
+}
+
+pub fn update_local_only_indicator(local_only: bool) {
+    debug!("[update_local_only_indicator] local only: {}", local_only);
+    if let Some(window) = get_main_window() {
+        let _ = window.emit("local_only", local_only);
+    }
+}
+
 pub fn hide_overlay() {
     debug!("[hide_overlay] hiding overlay window");
     if let Some(window) = get_main_window() {
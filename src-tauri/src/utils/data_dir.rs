@@ -0,0 +1,152 @@
+//! 数据目录: input.db、保险库、密钥文件、哈希盐、索引和日志都放在这里, 不再依赖工作目录
+//! 解析顺序: 命令行 --data-dir, 环境变量 AINPUT_DATA_DIR, 配置 storage.data_dir, 最后是 %APPDATA%\ainput
+//! 便携模式: exe 旁存在 portable 文件时, 默认数据目录为 exe 所在目录
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+
+const APP_DIR_NAME: &str = "ainput";
+pub const DATA_DIR_ENV: &str = "AINPUT_DATA_DIR";
+const DATA_DIR_ARG: &str = "--data-dir";
+const PORTABLE_MARKER: &str = "portable";
+const CONFIG_FILE: &str = "config.toml";
+
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe().ok().and_then(|p| p.parent().map(Path::to_path_buf))
+}
+
+// 旧版本的数据位置: release 下启动时会切换到 exe 所在目录, 开发时为工作目录
+fn legacy_dir() -> Option<PathBuf> {
+    if cfg!(debug_assertions) {
+        std::env::current_dir().ok()
+    } else {
+        exe_dir()
+    }
+}
+
+pub fn is_portable() -> bool {
+    exe_dir().map_or(false, |dir| dir.join(PORTABLE_MARKER).exists())
+}
+
+fn from_args() -> Option<PathBuf> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|a| a == DATA_DIR_ARG)
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from)
+}
+
+fn from_env() -> Option<PathBuf> {
+    std::env::var_os(DATA_DIR_ENV).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+fn platform_dir() -> PathBuf {
+    if is_portable() {
+        if let Some(dir) = exe_dir() {
+            return dir;
+        }
+    }
+    match std::env::var_os("APPDATA").filter(|v| !v.is_empty()) {
+        Some(appdata) => PathBuf::from(appdata).join(APP_DIR_NAME),
+        None => legacy_dir().unwrap_or_else(|| PathBuf::from(".")),
+    }
+}
+
+// 读取配置之前可以确定的数据目录(不含配置项), 也用于查找 config.toml
+fn resolve_without_config() -> PathBuf {
+    from_args().or_else(from_env).unwrap_or_else(platform_dir)
+}
+
+fn resolve(configured: &str) -> PathBuf {
+    from_args()
+        .or_else(from_env)
+        .or_else(|| (!configured.trim().is_empty()).then(|| PathBuf::from(configured.trim())))
+        .unwrap_or_else(platform_dir)
+}
+
+/// 可写的 config.toml 位置: 读取配置之前就能确定的数据目录, 是 config_candidates 中的第一个
+/// 配置了 storage.data_dir 时也不放在那里, 否则下次启动读取配置前找不到它
+pub fn writable_config_path() -> PathBuf {
+    resolve_without_config().join(CONFIG_FILE)
+}
+
+/// config.toml 的候选位置, 依次为数据目录、exe 所在目录, 开发时还有工作目录下的几个相对路径
+pub fn config_candidates() -> Vec<PathBuf> {
+    let mut candidates = vec![writable_config_path()];
+    if let Some(dir) = exe_dir() {
+        candidates.push(dir.join(CONFIG_FILE));
+    }
+    if cfg!(debug_assertions) {
+        candidates.extend(["config.toml", "src-tauri/config.toml", "../config.toml"].map(PathBuf::from));
+    }
+    candidates
+}
+
+/// 读取配置后调用一次, 确定并创建数据目录
+pub fn init(configured: &str) -> Result<PathBuf, String> {
+    let dir = resolve(configured);
+    fs::create_dir_all(&dir).map_err(|e| format!("failed to create data directory {:?}: {}", dir, e))?;
+    DATA_DIR.set(dir.clone()).map_err(|_| "data directory already initialized".to_string())?;
+    Ok(dir)
+}
+
+pub fn dir() -> &'static Path {
+    DATA_DIR.get_or_init(resolve_without_config)
+}
+
+/// 数据目录下的文件; 配置中的绝对路径原样返回
+pub fn path(name: &str) -> PathBuf {
+    let p = Path::new(name);
+    if p.is_absolute() {
+        p.to_path_buf()
+    } else {
+        dir().join(p)
+    }
+}
+
+pub fn path_str(name: &str) -> String {
+    path(name).to_string_lossy().to_string()
+}
+
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // 跨盘或安装目录只读时改为复制, 旧文件保留
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        if let Err(e) = fs::remove_file(from) {
+            warn!("[data_dir::migrate_legacy] copied {:?} but could not remove it: {}", from, e);
+        }
+    }
+    Ok(())
+}
+
+/// 旧版本把数据放在 exe 旁(或工作目录), 数据目录中还没有 input.db 时把 input.db 和 files 移过来
+/// input.db 最后移动, 中途失败时下次启动会重新迁移; 搜索索引不迁移, 启动时会从数据库重建
+pub fn migrate_legacy(files: &[&str]) -> Result<(), String> {
+    let Some(legacy) = legacy_dir() else { return Ok(()) };
+    let target = dir();
+    if fs::canonicalize(&legacy).ok() == fs::canonicalize(target).ok() {
+        return Ok(());
+    }
+    if !legacy.join("input.db").exists() || path("input.db").exists() {
+        return Ok(());
+    }
+    info!("[data_dir::migrate_legacy] moving data from {:?} to {:?}", legacy, target);
+    for name in files.iter().chain(["input.db-wal", "input.db-shm", "input.db"].iter()) {
+        let p = Path::new(name);
+        let from = legacy.join(p);
+        if p.is_absolute() || !from.exists() {
+            continue;
+        }
+        move_file(&from, &target.join(p)).map_err(|e| format!("failed to move {:?} to {:?}: {}", from, target, e))?;
+        info!("[data_dir::migrate_legacy] moved {}", name);
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::fs;
+
+use log::error;
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+const SALT_FILE: &str = "hash.salt";
+
+// 本机随机盐, 首次使用时生成, 保证同一取值在本机上得到稳定的哈希, 又无法被跨机器比对或查表还原
+static SALT: Lazy<Vec<u8>> = Lazy::new(load_or_create_salt);
+
+fn load_or_create_salt() -> Vec<u8> {
+    let salt_path = super::data_dir::path(SALT_FILE);
+    if let Ok(salt) = fs::read(&salt_path) {
+        if !salt.is_empty() {
+            return salt;
+        }
+    }
+    let mut salt = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+    if let Err(e) = fs::write(&salt_path, &salt) {
+        error!("[load_or_create_salt] failed to write salt file: {}", e);
+    }
+    salt
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 加盐哈希, 用于审计日志等只需要比对、不应保留原文的场景
+pub fn salted_hash(text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(SALT.as_slice());
+    hasher.update(text.as_bytes());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,22 @@
+//! 通知等后端文案的语言: 跟随 Windows 显示语言, 中文系统显示中文, 其余显示英文
+
+use windows::Win32::Globalization::GetUserDefaultUILanguage;
+
+// LANGID 低 10 位为主语言
+const PRIMARY_LANG_MASK: u16 = 0x3ff;
+const LANG_CHINESE: u16 = 0x04;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiLang {
+    Zh,
+    En,
+}
+
+pub fn ui_lang() -> UiLang {
+    let lang_id = unsafe { GetUserDefaultUILanguage() };
+    if lang_id & PRIMARY_LANG_MASK == LANG_CHINESE {
+        UiLang::Zh
+    } else {
+        UiLang::En
+    }
+}
@@ -1,6 +1,30 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use flexi_logger::{Cleanup, Criterion, FileSpec, Logger, Naming};
 
-pub fn init_logger(log_level: String) -> Result<(), Box<dyn std::error::Error>> {
+use crate::utils::hash::salted_hash;
+
+// 是否在日志中输出用户内容(输入内容、窗口标题、剪贴板、prompt 等), release 下默认关闭
+static LOG_USER_CONTENT: AtomicBool = AtomicBool::new(false);
+
+// 日志中的用户内容默认替换为长度和哈希摘要, 仅开发构建或开启诊断日志时输出原文
+pub fn redact(text: &str) -> String {
+    if LOG_USER_CONTENT.load(Ordering::Relaxed) {
+        return text.to_string();
+    }
+    format!("<redacted len={} hash={}>", text.chars().count(), &salted_hash(text)[..8])
+}
+
+pub fn redact_debug<T: Debug>(value: &T) -> String {
+    if LOG_USER_CONTENT.load(Ordering::Relaxed) {
+        return format!("{:?}", value);
+    }
+    redact(&format!("{:?}", value))
+}
+
+pub fn init_logger(log_level: String, diagnostic_logging: bool) -> Result<(), Box<dyn std::error::Error>> {
+    LOG_USER_CONTENT.store(cfg!(debug_assertions) || diagnostic_logging, Ordering::Relaxed);
     #[cfg(debug_assertions)] {
         Logger::try_with_str(log_level)?
         .log_to_stdout()
@@ -19,7 +43,7 @@ pub fn init_logger(log_level: String) -> Result<(), Box<dyn std::error::Error>>
     }
     #[cfg(not(debug_assertions))] {
         Logger::try_with_str(log_level)?
-        .log_to_file(FileSpec::default().directory("logs").basename("screen-buoy"))
+        .log_to_file(FileSpec::default().directory(super::data_dir::path("logs")).basename("screen-buoy"))
         .rotate(
             Criterion::Size(3_000_000),
             Naming::Numbers,
@@ -1 +1,4 @@
 pub mod logger;
+pub mod hash;
+pub mod data_dir;
+pub mod locale;
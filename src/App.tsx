@@ -8,6 +8,10 @@ function App() {
   const candidateDivRef = useRef<HTMLDivElement>(null)
   const candidateRef = useRef('')
   const [cssText, setCssText] = useState('')
+  // 仅本地模式指示
+  const [localOnly, setLocalOnly] = useState(false)
+  // 等待用户授权的应用
+  const [consentApp, setConsentApp] = useState<string | null>(null)
 
   // 监听 Tauri 事件
   useEffect(() => {
@@ -15,6 +19,8 @@ function App() {
     let unlistenUpdate: (() => void) | null = null
     let unlistenHide: (() => void) | null = null
     let unlistenSelectCandidate: (() => void) | null = null
+    let unlistenLocalOnly: (() => void) | null = null
+    let unlistenConsent: (() => void) | null = null
     let cancelled = false
 
     import('@tauri-apps/api/event').then(({ listen }) => {
@@ -32,6 +38,7 @@ function App() {
       listen('hide_overlay', () => {
         console.info('[App] event: hide_overlay')
         setOverlayVisible(false)
+        setConsentApp(null)
         setCandidate('')
       }).then((unlisten) => {
         if (cancelled) unlisten()
@@ -55,6 +62,21 @@ function App() {
         if (cancelled) unlisten()
         else unlistenSelectCandidate = unlisten
       })
+      listen('local_only', (event: any) => {
+        console.info(`[App] event: local_only, payload: ${event.payload}`)
+        setLocalOnly(!!event.payload)
+      }).then((unlisten) => {
+        if (cancelled) unlisten()
+        else unlistenLocalOnly = unlisten
+      })
+      listen('consent_request', (event: any) => {
+        console.info(`[App] event: consent_request, app: ${event.payload}`)
+        setConsentApp(String(event.payload))
+        setOverlayVisible(true)
+      }).then((unlisten) => {
+        if (cancelled) unlisten()
+        else unlistenConsent = unlisten
+      })
     })
     // 去掉主窗口滚动条
     document.body.style.overflow = 'hidden';
@@ -64,6 +86,8 @@ function App() {
       unlistenUpdate && unlistenUpdate()
       unlistenHide && unlistenHide()
       unlistenSelectCandidate && unlistenSelectCandidate()
+      unlistenLocalOnly && unlistenLocalOnly()
+      unlistenConsent && unlistenConsent()
       document.body.style.overflow = '';
     }
   }, [])
@@ -79,10 +103,21 @@ function App() {
   }, [])
 
   useEffect(() => {
-    if (overlayVisible && candidate) {
+    invoke('get_local_only_command').then((v: any) => setLocalOnly(!!v))
+  }, [])
+
+  useEffect(() => {
+    if (overlayVisible && (candidate || consentApp)) {
       setTimeout(resizeToFitContent, 0)
     }
-  }, [candidate, overlayVisible])
+  }, [candidate, consentApp, overlayVisible])
+
+  function decideConsent(decision: string) {
+    if (!consentApp) return
+    console.info(`[App] consent for ${consentApp}: ${decision}`)
+    invoke('set_app_consent_command', { app: consentApp, decision })
+    setConsentApp(null)
+  }
 
   useEffect(() => {
     candidateRef.current = candidate
@@ -125,7 +160,16 @@ function App() {
             whiteSpace: 'nowrap',
           }}
         >
-          <div id="candidate" ref={candidateDivRef} className="hint">{candidate}</div>
+          {consentApp ? (
+            <div id="consent" ref={candidateDivRef} className="hint">
+              Allow ainput in {consentApp}?{' '}
+              <button onClick={() => decideConsent('Allow')}>Allow</button>{' '}
+              <button onClick={() => decideConsent('Deny')}>Deny</button>{' '}
+              <button onClick={() => decideConsent('LocalOnly')}>Local only</button>
+            </div>
+          ) : (
+            <div id="candidate" ref={candidateDivRef} className="hint">{localOnly && <span title="local only">⌂ </span>}{candidate}</div>
+          )}
         </div>
       )}
     </>
@@ -0,0 +1,106 @@
+import React, { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+
+const containerStyle: React.CSSProperties = {
+  width: '100vw',
+  height: '100vh',
+  minWidth: 0,
+  minHeight: 0,
+  background: '#181820',
+  boxSizing: 'border-box',
+  display: 'flex',
+  flexDirection: 'column',
+  padding: '16px',
+  overflow: 'auto',
+};
+const labelStyle: React.CSSProperties = {
+  fontSize: 13,
+  color: '#e0e0e0',
+  marginBottom: 2,
+  fontWeight: 500,
+};
+const preStyle: React.CSSProperties = {
+  fontSize: 12,
+  background: '#23232b',
+  color: '#fff',
+  borderRadius: 6,
+  padding: '6px 8px',
+  whiteSpace: 'pre-wrap',
+  wordBreak: 'break-all',
+  marginTop: 0,
+  marginBottom: 10,
+};
+const buttonStyle: React.CSSProperties = {
+  fontSize: 13,
+  padding: '4px 12px',
+  border: 'none',
+  borderRadius: 6,
+  background: '#23232b',
+  color: '#fff',
+  alignSelf: 'flex-end',
+  cursor: 'pointer',
+};
+
+const Preview: React.FC = () => {
+  const [preview, setPreview] = useState<any>(null);
+  const [error, setError] = useState('');
+
+  const refresh = () => {
+    invoke('preview_request_command')
+      .then((p: any) => {
+        setPreview(p);
+        setError('');
+      })
+      .catch((e: any) => setError(String(e)));
+  };
+
+  useEffect(() => {
+    refresh();
+  }, []);
+
+  let body = preview?.body || '';
+  try {
+    body = JSON.stringify(JSON.parse(body), null, 2);
+  } catch {
+    // CMD 的请求体是纯文本
+  }
+
+  return (
+    <div style={containerStyle}>
+      <button style={buttonStyle} onClick={refresh}>Refresh</button>
+      {error && <pre style={preStyle}>{error}</pre>}
+      {preview && (
+        <>
+          <div style={labelStyle}>Provider</div>
+          <pre style={preStyle}>{preview.provider}</pre>
+          <div style={labelStyle}>Target</div>
+          <pre style={preStyle}>{preview.target}</pre>
+          <div style={labelStyle}>Headers</div>
+          <pre style={preStyle}>{JSON.stringify(preview.headers, null, 2)}</pre>
+          <div style={labelStyle}>Redactions</div>
+          <pre style={preStyle}>{preview.redactions}</pre>
+          <div style={labelStyle}>Context Selection</div>
+          <pre style={preStyle}>
+            {(preview.selection || [])
+              .map((item: any) => {
+                const s = item.signals;
+                const flags = [
+                  s.same_field && 'field',
+                  s.same_window && 'window',
+                  s.same_app && 'app',
+                  s.accepted && 'accepted',
+                  s.pinned && 'pinned',
+                ].filter(Boolean).join(',');
+                return `${item.included ? '✓' : '✗'} ${item.section} ${item.id} score=${item.score.toFixed(2)} tokens=${item.tokens}${item.truncated ? ' (truncated)' : ''} sim=${s.similarity.toFixed(2)} recency=${s.recency.toFixed(2)} ${flags}`;
+              })
+              .join('\n')}
+          </pre>
+          <div style={labelStyle}>Body</div>
+          <pre style={preStyle}>{body}</pre>
+        </>
+      )}
+    </div>
+  );
+};
+
+export default Preview;
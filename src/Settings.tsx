@@ -70,7 +70,7 @@ const Settings: React.FC = () => {
   useEffect(() => {
     let cancelled = false;
     const fetchToken = () => {
-      invoke('get_used_token_command', { apikey: config.ai_client?.api_key || '' }).then((used: any) => {
+      invoke('get_used_token_command').then((used: any) => {
         if (!cancelled) setConfig((prev: any) => ({
           ...prev,
           ai_client: { ...prev.ai_client, usedToken: String(used) }
@@ -3,6 +3,7 @@ import { createRoot } from 'react-dom/client'
 import './index.css'
 import App from './App.tsx'
 import Settings from './Settings'
+import Preview from './Preview'
 import { BrowserRouter, Routes, Route } from 'react-router-dom'
 
 console.info('[main] Initializing React application...')
@@ -12,6 +13,7 @@ createRoot(document.getElementById('root')!).render(
       <Routes>
         <Route path="/" element={<App />} />
         <Route path="/settings" element={<Settings />} />
+        <Route path="/preview" element={<Preview />} />
       </Routes>
     </BrowserRouter>
   </StrictMode>,